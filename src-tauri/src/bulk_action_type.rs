@@ -2,6 +2,9 @@ pub const HIDE: u8 = 0;
 pub const UNHIDE: u8 = 1;
 pub const FAVORITE: u8 = 2;
 pub const UNFAVORITE: u8 = 3;
+pub const HIDE_DEAD: u8 = 4;
+pub const ADD_TO_LIST: u8 = 5;
+pub const REMOVE_FROM_LIST: u8 = 6;
 
 pub const FIELD_HIDDEN: &str = "hidden";
 pub const FIELD_FAVORITE: &str = "favorite";
@@ -0,0 +1,371 @@
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, RwLock},
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use argon2::Argon2;
+use base64::{Engine, prelude::BASE64_STANDARD};
+use chacha20poly1305::{
+    ChaCha20Poly1305, Key, Nonce,
+    aead::{Aead, AeadCore, KeyInit, OsRng, rand_core::RngCore},
+};
+use keyring::Entry;
+
+use crate::{log::log, source_type, sql};
+
+const ENCRYPTED_PREFIX: &str = "enc:v1:";
+const KEYRING_SERVICE: &str = "open-tv";
+const KEYRING_USER: &str = "credentials-key";
+const PASSPHRASE_SALT: &str = "credentialsSalt";
+const PASSPHRASE_CHECK: &str = "credentialsCheck";
+const PASSPHRASE_CHECK_VALUE: &str = "open-tv";
+const NONCE_SIZE: usize = 12;
+const SALT_SIZE: usize = 16;
+/// Stands in for a password which can't be decrypted, update_source keeps the stored one
+pub const UNCHANGED_PASSWORD: &str = "__unchanged__";
+
+static KEY: LazyLock<RwLock<Option<Key>>> = LazyLock::new(|| RwLock::new(None));
+
+fn get_cached_key() -> Result<Option<Key>> {
+    Ok(*KEY
+        .read()
+        .map_err(|_| anyhow!("credentials key lock poisoned"))?)
+}
+
+fn set_cached_key(key: Option<Key>) -> Result<()> {
+    *KEY.write()
+        .map_err(|_| anyhow!("credentials key lock poisoned"))? = key;
+    Ok(())
+}
+
+fn uses_passphrase() -> Result<bool> {
+    Ok(sql::get_settings()?.contains_key(PASSPHRASE_SALT))
+}
+
+fn get_key() -> Result<Key> {
+    if let Some(key) = get_cached_key()? {
+        return Ok(key);
+    }
+    if uses_passphrase()? {
+        bail!("Credentials are locked, please enter your passphrase");
+    }
+    let key = get_keyring_key()
+        .context("OS keyring unavailable, set a passphrase to store credentials encrypted")?;
+    set_cached_key(Some(key))?;
+    Ok(key)
+}
+
+fn get_keyring_key() -> Result<Key> {
+    let entry = Entry::new(KEYRING_SERVICE, KEYRING_USER)?;
+    match entry.get_password() {
+        Ok(encoded) => {
+            let bytes = BASE64_STANDARD.decode(encoded)?;
+            if bytes.len() != 32 {
+                bail!("Invalid credentials key in OS keyring");
+            }
+            Ok(*Key::from_slice(&bytes))
+        }
+        Err(keyring::Error::NoEntry) => {
+            let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+            entry.set_password(&BASE64_STANDARD.encode(key))?;
+            Ok(key)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("Failed to derive key from passphrase: {e}"))?;
+    Ok(key)
+}
+
+fn encrypt_with(key: &Key, value: &str) -> Result<String> {
+    let cipher = ChaCha20Poly1305::new(key);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let mut data = nonce.to_vec();
+    data.extend(
+        cipher
+            .encrypt(&nonce, value.as_bytes())
+            .map_err(|_| anyhow!("Failed to encrypt credentials"))?,
+    );
+    Ok(format!(
+        "{ENCRYPTED_PREFIX}{}",
+        BASE64_STANDARD.encode(data)
+    ))
+}
+
+fn decrypt_with(key: &Key, encoded: &str) -> Result<String> {
+    let data = BASE64_STANDARD.decode(encoded)?;
+    if data.len() < NONCE_SIZE {
+        bail!("Invalid encrypted credentials");
+    }
+    let (nonce, ciphertext) = data.split_at(NONCE_SIZE);
+    let value = ChaCha20Poly1305::new(key)
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow!("Failed to decrypt credentials"))?;
+    Ok(String::from_utf8(value)?)
+}
+
+fn is_encrypted(value: &str) -> bool {
+    value.starts_with(ENCRYPTED_PREFIX)
+}
+
+pub fn encrypt(value: &str) -> Result<String> {
+    if is_encrypted(value) {
+        return Ok(value.to_string());
+    }
+    encrypt_with(&get_key()?, value)
+}
+
+pub fn decrypt(value: &str) -> Result<String> {
+    let Some(encoded) = value.strip_prefix(ENCRYPTED_PREFIX) else {
+        return Ok(value.to_string());
+    };
+    decrypt_with(&get_key()?, encoded)
+}
+
+pub fn encrypt_password(password: Option<String>) -> Result<Option<String>> {
+    password.map(|p| encrypt(&p)).transpose()
+}
+
+/// Passwords which can't be decrypted come out as UNCHANGED_PASSWORD, so saving the source
+/// while locked doesn't wipe them
+pub fn decrypt_password(password: Option<String>) -> Option<String> {
+    password.map(|p| {
+        decrypt(&p)
+            .inspect_err(|e| log(format!("{:?}", e)))
+            .unwrap_or_else(|_| UNCHANGED_PASSWORD.to_string())
+    })
+}
+
+pub fn get_password(password: Option<&String>) -> Result<&str> {
+    match password.map(String::as_str) {
+        Some(UNCHANGED_PASSWORD) => bail!("Credentials are locked, please enter your passphrase"),
+        Some(password) => Ok(password),
+        None => bail!("Missing password"),
+    }
+}
+
+pub fn is_locked() -> Result<bool> {
+    Ok(get_cached_key()?.is_none() && uses_passphrase()?)
+}
+
+pub fn unlock(passphrase: String) -> Result<()> {
+    let settings = sql::get_settings()?;
+    let salt = BASE64_STANDARD.decode(
+        settings
+            .get(PASSPHRASE_SALT)
+            .context("No passphrase has been set")?,
+    )?;
+    let key = derive_key(&passphrase, &salt)?;
+    let check = settings
+        .get(PASSPHRASE_CHECK)
+        .and_then(|c| c.strip_prefix(ENCRYPTED_PREFIX))
+        .context("Missing passphrase check value")?;
+    if decrypt_with(&key, check).ok().as_deref() != Some(PASSPHRASE_CHECK_VALUE) {
+        bail!("Invalid passphrase");
+    }
+    set_cached_key(Some(key))
+}
+
+pub fn set_passphrase(passphrase: Option<String>) -> Result<()> {
+    let passwords = sql::get_source_passwords()?
+        .into_iter()
+        .map(|(id, password)| Ok((id, decrypt(&password)?)))
+        .collect::<Result<Vec<(i64, String)>>>()?;
+    let mut settings: HashMap<String, Option<String>> = HashMap::new();
+    let new_key = match passphrase {
+        Some(passphrase) => {
+            let mut salt = [0u8; SALT_SIZE];
+            OsRng.fill_bytes(&mut salt);
+            let key = derive_key(&passphrase, &salt)?;
+            settings.insert(
+                PASSPHRASE_SALT.to_string(),
+                Some(BASE64_STANDARD.encode(salt)),
+            );
+            settings.insert(
+                PASSPHRASE_CHECK.to_string(),
+                Some(encrypt_with(&key, PASSPHRASE_CHECK_VALUE)?),
+            );
+            key
+        }
+        None => {
+            settings.insert(PASSPHRASE_SALT.to_string(), None);
+            settings.insert(PASSPHRASE_CHECK.to_string(), None);
+            get_keyring_key().context("OS keyring unavailable, the passphrase is still needed")?
+        }
+    };
+    sql::do_tx(|tx| {
+        for (id, password) in passwords {
+            sql::set_source_password(tx, id, &encrypt_with(&new_key, &password)?)?;
+        }
+        Ok(())
+    })?;
+    sql::update_settings(settings)?;
+    set_cached_key(Some(new_key))
+}
+
+pub fn migrate_credentials() -> Result<()> {
+    let passwords: HashMap<i64, String> = sql::get_source_passwords()?
+        .into_iter()
+        .filter(|(_, password)| !is_encrypted(password))
+        .collect();
+    if passwords.is_empty() {
+        return Ok(());
+    }
+    let key = get_key()?;
+    let sources = sql::get_sources()?;
+    sql::do_tx(|tx| {
+        for source in sources {
+            let Some(id) = source.id else {
+                continue;
+            };
+            let Some(password) = passwords.get(&id) else {
+                continue;
+            };
+            if source.source_type == source_type::XTREAM
+                && let Some(username) = source.username.as_ref()
+            {
+                sql::replace_url_password(tx, id, username, password)?;
+            }
+            sql::set_source_password(tx, id, &encrypt_with(&key, password)?)?;
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod test_crypto {
+    use rusqlite::params;
+
+    use super::*;
+    use crate::sql::test_db;
+
+    fn set_stored(source_id: i64, password: &str) {
+        sql::do_tx(|tx| sql::set_source_password(tx, source_id, password)).unwrap();
+    }
+
+    fn get_stored(source_id: i64) -> Option<String> {
+        sql::get_source_passwords()
+            .unwrap()
+            .into_iter()
+            .find(|(id, _)| *id == source_id)
+            .map(|(_, password)| password)
+    }
+
+    fn use_random_key() {
+        set_cached_key(Some(ChaCha20Poly1305::generate_key(&mut OsRng))).unwrap();
+    }
+
+    fn clean_up(source_id: i64) {
+        sql::delete_source(source_id).unwrap();
+        sql::update_settings(HashMap::from([
+            (PASSPHRASE_SALT.to_string(), None),
+            (PASSPHRASE_CHECK.to_string(), None),
+        ]))
+        .unwrap();
+        set_cached_key(None).unwrap();
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+        let encrypted = encrypt_with(&key, "secret").unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert_ne!(encrypted, encrypt_with(&key, "secret").unwrap());
+        let encoded = encrypted.strip_prefix(ENCRYPTED_PREFIX).unwrap();
+        assert_eq!(decrypt_with(&key, encoded).unwrap(), "secret");
+        let other = ChaCha20Poly1305::generate_key(&mut OsRng);
+        assert!(decrypt_with(&other, encoded).is_err());
+    }
+
+    #[test]
+    fn test_passphrase() {
+        let _lock = test_db::lock();
+        use_random_key();
+        let source_id = test_db::insert_source("crypto passphrase");
+        set_stored(source_id, "secret");
+        set_passphrase(Some("first".to_string())).unwrap();
+        let first = get_stored(source_id).unwrap();
+        assert!(is_encrypted(&first));
+
+        set_cached_key(None).unwrap();
+        assert!(is_locked().unwrap());
+        assert!(decrypt(&first).is_err());
+        assert_eq!(
+            decrypt_password(Some(first.clone())).as_deref(),
+            Some(UNCHANGED_PASSWORD)
+        );
+        assert!(unlock("wrong".to_string()).is_err());
+        unlock("first".to_string()).unwrap();
+        assert_eq!(decrypt(&first).unwrap(), "secret");
+
+        set_passphrase(Some("second".to_string())).unwrap();
+        let second = get_stored(source_id).unwrap();
+        assert_ne!(first, second);
+        set_cached_key(None).unwrap();
+        assert!(unlock("first".to_string()).is_err());
+        unlock("second".to_string()).unwrap();
+        assert_eq!(decrypt(&second).unwrap(), "secret");
+        clean_up(source_id);
+    }
+
+    #[test]
+    fn test_migrate_credentials() {
+        let _lock = test_db::lock();
+        use_random_key();
+        let source_id = test_db::insert_source("crypto migrate");
+        let channel_id = test_db::insert_channel(source_id, "Migrated").id.unwrap();
+        let sql = sql::get_conn().unwrap();
+        sql.execute(
+            "UPDATE sources SET source_type = ?, username = 'user' WHERE id = ?",
+            params![source_type::XTREAM, source_id],
+        )
+        .unwrap();
+        sql.execute(
+            "UPDATE channels SET url = 'http://localhost/live/user/secret/1.ts' WHERE id = ?",
+            params![channel_id],
+        )
+        .unwrap();
+        set_stored(source_id, "secret");
+        migrate_credentials().unwrap();
+
+        let stored = get_stored(source_id).unwrap();
+        assert!(is_encrypted(&stored));
+        assert_eq!(decrypt(&stored).unwrap(), "secret");
+        let url: String = sql
+            .query_row(
+                "SELECT url FROM channels WHERE id = ?",
+                params![channel_id],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(url, "http://localhost/live/user/{password}/1.ts");
+        clean_up(source_id);
+    }
+
+    #[test]
+    fn test_update_source_password() {
+        let _lock = test_db::lock();
+        use_random_key();
+        let source_id = test_db::insert_source("crypto update");
+        set_stored(source_id, "secret");
+        let mut source = sql::get_source_from_id(source_id).unwrap();
+        assert_eq!(source.password.as_deref(), Some("secret"));
+
+        source.password = Some(UNCHANGED_PASSWORD.to_string());
+        sql::update_source(source.clone()).unwrap();
+        assert_eq!(decrypt(&get_stored(source_id).unwrap()).unwrap(), "secret");
+        source.password = Some("changed".to_string());
+        sql::update_source(source.clone()).unwrap();
+        assert_eq!(decrypt(&get_stored(source_id).unwrap()).unwrap(), "changed");
+        source.password = None;
+        sql::update_source(source).unwrap();
+        assert_eq!(get_stored(source_id), None);
+        clean_up(source_id);
+    }
+}
@@ -0,0 +1,81 @@
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+
+use crate::{log::log, types::ChannelDrm};
+
+const CLEARKEY_LICENSE_TYPES: [&str; 2] = ["clearkey", "org.w3.clearkey"];
+const KEY_HEX_LENGTH: usize = 32;
+
+pub fn get_clearkey(drm: &ChannelDrm) -> Option<String> {
+    if let Some(license_type) = drm.license_type.as_ref()
+        && !CLEARKEY_LICENSE_TYPES.contains(&license_type.trim().to_lowercase().as_str())
+    {
+        log(format!("Unsupported DRM license type: {license_type}"));
+        return None;
+    }
+    let license_key = drm.license_key.as_ref()?.trim();
+    let key = if license_key.starts_with('{') {
+        get_json_key(license_key)
+    } else {
+        license_key.split(',').next().map(|pair| {
+            pair.rsplit(':')
+                .next()
+                .unwrap_or(pair)
+                .trim()
+                .to_lowercase()
+        })
+    };
+    let key = key.filter(|key| is_valid_key(key));
+    if key.is_none() {
+        log(format!("Unsupported ClearKey license key: {license_key}"));
+    }
+    key
+}
+
+fn get_json_key(license_key: &str) -> Option<String> {
+    let json: serde_json::Value = serde_json::from_str(license_key).ok()?;
+    let key = json.get("keys")?.get(0)?.get("k")?.as_str()?;
+    let bytes = BASE64_URL_SAFE_NO_PAD
+        .decode(key.trim_end_matches('='))
+        .ok()?;
+    Some(bytes.iter().map(|b| format!("{b:02x}")).collect())
+}
+
+fn is_valid_key(key: &str) -> bool {
+    key.len() == KEY_HEX_LENGTH && key.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod test_drm {
+    use super::get_clearkey;
+    use crate::types::ChannelDrm;
+
+    fn drm(license_type: Option<&str>, license_key: &str) -> ChannelDrm {
+        ChannelDrm {
+            channel_id: None,
+            license_type: license_type.map(|t| t.to_string()),
+            license_key: Some(license_key.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_get_clearkey() {
+        assert_eq!(
+            get_clearkey(&drm(
+                Some("clearkey"),
+                "0123456789ABCDEF0123456789abcdef:FEDCBA9876543210fedcba9876543210"
+            ))
+            .as_deref(),
+            Some("fedcba9876543210fedcba9876543210")
+        );
+        assert_eq!(
+            get_clearkey(&drm(
+                Some("org.w3.clearkey"),
+                r#"{"keys":[{"kty":"oct","k":"ASNFZ4mrze8BI0VniavN7w","kid":"AAAAAAAAAAAAAAAAAAAAAA"}]}"#
+            ))
+            .as_deref(),
+            Some("0123456789abcdef0123456789abcdef")
+        );
+        assert!(get_clearkey(&drm(Some("com.widevine.alpha"), "https://license.local")).is_none());
+        assert!(get_clearkey(&drm(Some("clearkey"), "https://license.local/key")).is_none());
+    }
+}
@@ -0,0 +1,204 @@
+use std::{collections::HashMap, process::Stdio, sync::Arc, time::Duration};
+
+use anyhow::{Context, Result, bail};
+use tauri::{AppHandle, Emitter, State};
+use tokio::{
+    process::Command,
+    sync::{Mutex, Semaphore},
+    task::JoinSet,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    log::log,
+    sql,
+    types::{AppState, Channel, ChannelHttpHeaders, HealthCheckProgress, Source},
+    utils::{get_bin, get_http_headers, get_http_proxy, get_stream_client, to_ffmpeg_headers},
+    xtream,
+};
+
+const MAX_CONCURRENT_CHECKS: usize = 10;
+const CHECK_TIMEOUT_SECS: u64 = 10;
+const FFPROBE_BIN_NAME: &str = "ffprobe";
+const HEALTH_CHECK_PROGRESS_EVENT: &str = "health_check_progress";
+#[cfg(target_os = "windows")]
+const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+pub async fn check_health(
+    app: AppHandle,
+    state: State<'_, Mutex<AppState>>,
+    source_ids: Vec<i64>,
+    use_ffprobe: bool,
+) -> Result<()> {
+    let token = CancellationToken::new();
+    if let Some(previous) = state.lock().await.health_check_stop.replace(token.clone()) {
+        previous.cancel();
+    }
+    let sources: HashMap<i64, Source> = source_ids
+        .iter()
+        .filter_map(|id| sql::get_source_from_id(*id).ok().map(|s| (*id, s)))
+        .collect();
+    if use_ffprobe {
+        // Fail upfront instead of flagging every channel of the source as dead
+        for source in sources.values() {
+            get_http_proxy(source)?;
+        }
+    }
+    let channels = sql::get_health_check_channels(&source_ids)?;
+    let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_CHECKS));
+    // Providers cap concurrent connections per account, going over gets healthy channels refused
+    let source_semaphores: HashMap<i64, Arc<Semaphore>> = sources
+        .iter()
+        .map(|(id, source)| (*id, Arc::new(Semaphore::new(get_max_checks(source)))))
+        .collect();
+    let mut set = JoinSet::new();
+    for channel in channels {
+        let Some(source_id) = channel.source_id else {
+            continue;
+        };
+        let (Some(source), Some(source_semaphore)) = (
+            sources.get(&source_id).cloned(),
+            source_semaphores.get(&source_id).cloned(),
+        ) else {
+            continue;
+        };
+        let semaphore = semaphore.clone();
+        let token = token.clone();
+        set.spawn(async move {
+            let _source_permit = source_semaphore.acquire_owned().await.ok()?;
+            let _permit = semaphore.acquire_owned().await.ok()?;
+            tokio::select! {
+                ok = check_channel(&channel, &source, use_ffprobe) => Some((channel, ok)),
+                _ = token.cancelled() => None,
+            }
+        });
+    }
+    let mut progress = HealthCheckProgress {
+        checked: 0,
+        total: set.len(),
+        dead: 0,
+    };
+    while let Some(result) = set.join_next().await {
+        let Ok(Some((channel, ok))) = result else {
+            continue;
+        };
+        if let (Some(source_id), Some(url)) = (channel.source_id, channel.url.as_ref()) {
+            _ = sql::set_channel_health(source_id, url, ok).map_err(|e| log(format!("{:?}", e)));
+        }
+        progress.checked += 1;
+        if !ok {
+            progress.dead += 1;
+        }
+        let _ = app.emit(HEALTH_CHECK_PROGRESS_EVENT, progress.clone());
+    }
+    if token.is_cancelled() {
+        bail!("health check aborted");
+    }
+    state.lock().await.health_check_stop = None;
+    Ok(())
+}
+
+pub async fn cancel_health_check(state: State<'_, Mutex<AppState>>) -> Result<()> {
+    let token = state
+        .lock()
+        .await
+        .health_check_stop
+        .take()
+        .context("no health check running")?;
+    token.cancel();
+    Ok(())
+}
+
+fn get_max_checks(source: &Source) -> usize {
+    source
+        .max_streams
+        .filter(|max| *max > 0)
+        .map(usize::from)
+        .unwrap_or(MAX_CONCURRENT_CHECKS)
+        .min(MAX_CONCURRENT_CHECKS)
+}
+
+async fn check_channel(channel: &Channel, source: &Source, use_ffprobe: bool) -> bool {
+    let url = match channel
+        .url
+        .clone()
+        .and_then(|url| xtream::resolve_url(url, Some(source)).ok())
+    {
+        Some(url) => url,
+        None => return false,
+    };
+    let headers = channel
+        .id
+        .and_then(|id| sql::get_channel_headers_by_id(id).ok())
+        .flatten();
+    let is_http = url.starts_with("http://") || url.starts_with("https://");
+    if is_http && probe_http(&url, headers.clone(), source).await.is_err() {
+        return false;
+    }
+    if use_ffprobe {
+        return probe_ffprobe(&url, headers, source).await.is_ok();
+    }
+    true
+}
+
+async fn probe_http(url: &str, headers: Option<ChannelHttpHeaders>, source: &Source) -> Result<()> {
+    let client = get_stream_client(headers, source)?;
+    let mut response = client
+        .get(url)
+        .timeout(Duration::from_secs(CHECK_TIMEOUT_SECS))
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        bail!("HTTP {}", response.status());
+    }
+    match response.chunk().await? {
+        Some(chunk) if !chunk.is_empty() => Ok(()),
+        _ => bail!("empty response"),
+    }
+}
+
+async fn probe_ffprobe(
+    url: &str,
+    headers: Option<ChannelHttpHeaders>,
+    source: &Source,
+) -> Result<()> {
+    let http_headers = get_http_headers(headers.as_ref(), source.id);
+    let headers = headers.unwrap_or_default();
+    let mut command = Command::new(get_bin(FFPROBE_BIN_NAME));
+    command.arg("-v").arg("error");
+    if let Some(user_agent) = headers
+        .user_agent
+        .or_else(|| source.stream_user_agent.clone())
+    {
+        command.arg("-user_agent").arg(user_agent);
+    }
+    if let Some(http_headers) = to_ffmpeg_headers(&http_headers) {
+        command.arg("-headers").arg(http_headers);
+    }
+    if headers.ignore_ssl == Some(true) {
+        command.arg("-tls_verify").arg("0");
+    }
+    if let Some(proxy) = get_http_proxy(source)? {
+        command.arg("-http_proxy").arg(proxy);
+    }
+    #[cfg(target_os = "windows")]
+    command.creation_flags(CREATE_NO_WINDOW);
+    command
+        .arg("-show_entries")
+        .arg("stream=codec_type")
+        .arg("-of")
+        .arg("csv=p=0")
+        .arg(url)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true);
+    let output = tokio::time::timeout(
+        Duration::from_secs(CHECK_TIMEOUT_SECS * 2),
+        command.output(),
+    )
+    .await??;
+    if !output.status.success() || output.stdout.is_empty() {
+        bail!("ffprobe could not read stream");
+    }
+    Ok(())
+}
@@ -6,7 +6,8 @@ use tauri::{AppHandle, Manager, State};
 use tokio::sync::Mutex;
 use types::{
     AppState, Channel, CustomChannel, CustomChannelExtraData, EPG, EPGNotify, Filters, Group,
-    IdName, NetworkInfo, Settings, Source,
+    HttpHeader, IdName, NameRule, NetworkInfo, PlaybackOverrides, Profile, SavedSearch, SearchPage,
+    Settings, Source, UserList,
 };
 #[cfg(any(target_os = "macos", target_os = "windows"))]
 use {
@@ -18,14 +19,29 @@ use {
 };
 
 pub mod bulk_action_type;
+pub mod crypto;
+pub mod drm;
 pub mod epg;
+pub mod health;
 pub mod log;
+pub mod logical;
 pub mod m3u;
 pub mod media_type;
 pub mod mpv;
+pub mod mpv_ipc;
+pub mod name_rule_type;
+pub mod name_rules;
+pub mod parental;
+pub mod playback_error;
+pub mod playback_error_type;
+pub mod player;
+pub mod player_type;
+pub mod query;
+pub mod relevance;
 pub mod restream;
 pub mod settings;
 pub mod share;
+pub mod sleep;
 pub mod sort_type;
 pub mod source_type;
 pub mod sql;
@@ -33,6 +49,7 @@ pub mod types;
 pub mod utils;
 pub mod view_type;
 pub mod xtream;
+pub mod zapping;
 
 #[cfg(any(target_os = "macos", target_os = "windows"))]
 static ENABLE_TRAY_ICON: LazyLock<bool> = LazyLock::new(|| {
@@ -61,6 +78,7 @@ pub fn run() {
             get_settings,
             update_settings,
             search,
+            search_page,
             bulk_update,
             get_xtream,
             refresh_source,
@@ -113,6 +131,65 @@ pub fn run() {
             hide_channel,
             hide_group,
             remove_from_history,
+            get_name_rules,
+            set_name_rules,
+            get_logical_channel_members,
+            check_channels_health,
+            cancel_health_check,
+            start_sleep_timer,
+            start_sleep_timer_at_programme_end,
+            get_sleep_timer,
+            cancel_sleep_timer,
+            credentials_locked,
+            unlock_credentials,
+            set_credentials_passphrase,
+            get_source_headers,
+            set_source_headers,
+            get_saved_searches,
+            save_search,
+            delete_saved_search,
+            share_saved_search,
+            get_lists,
+            save_list,
+            delete_list,
+            add_to_list,
+            remove_from_list,
+            reorder_list,
+            get_channel_lists,
+            resume_playback,
+            get_watch_progress,
+            player_pause,
+            player_seek,
+            player_set_volume,
+            player_set_audio_track,
+            player_set_subtitle_track,
+            player_screenshot,
+            player_get_property,
+            zap_next,
+            zap_previous,
+            zap_to,
+            get_profiles,
+            get_active_profile,
+            save_profile,
+            delete_profile,
+            switch_profile,
+            has_parental_pin,
+            set_parental_pin,
+            parental_unlock,
+            parental_lock,
+            is_parental_unlocked,
+            lock_channel,
+            lock_group,
+            lock_source,
+            get_channel_playback_overrides,
+            set_channel_playback_overrides,
+            get_source_playback_overrides,
+            set_source_playback_overrides,
+            move_in_list,
+            move_channel,
+            reorder_channels,
+            move_group,
+            reorder_groups,
         ])
         .setup(|app| {
             app.manage(Mutex::new(AppState {
@@ -215,12 +292,112 @@ async fn play(
     record: bool,
     record_path: Option<String>,
     state: State<'_, Mutex<AppState>>,
+    app: AppHandle,
+) -> Result<(), String> {
+    let options = mpv::PlayOptions {
+        record,
+        record_path,
+        start: None,
+    };
+    mpv::play(channel, options, state, app)
+        .await
+        .map_err(map_err_frontend)
+}
+
+#[tauri::command]
+async fn resume_playback(
+    channel: Channel,
+    position: Option<f64>,
+    state: State<'_, Mutex<AppState>>,
+    app: AppHandle,
 ) -> Result<(), String> {
-    mpv::play(channel, record, record_path, state)
+    mpv::resume(channel, position, state, app)
+        .await
+        .map_err(map_err_frontend)
+}
+
+#[tauri::command]
+async fn player_pause(paused: Option<bool>) -> Result<(), String> {
+    mpv_ipc::pause(paused).await.map_err(map_err_frontend)
+}
+
+#[tauri::command]
+async fn player_seek(seconds: f64, absolute: bool) -> Result<(), String> {
+    mpv_ipc::seek(seconds, absolute)
+        .await
+        .map_err(map_err_frontend)
+}
+
+#[tauri::command]
+async fn player_set_volume(volume: u8) -> Result<(), String> {
+    mpv_ipc::set_volume(volume).await.map_err(map_err_frontend)
+}
+
+#[tauri::command]
+async fn player_set_audio_track(track_id: Option<i64>) -> Result<(), String> {
+    mpv_ipc::set_audio_track(track_id)
+        .await
+        .map_err(map_err_frontend)
+}
+
+#[tauri::command]
+async fn player_set_subtitle_track(track_id: Option<i64>) -> Result<(), String> {
+    mpv_ipc::set_subtitle_track(track_id)
+        .await
+        .map_err(map_err_frontend)
+}
+
+#[tauri::command]
+async fn player_screenshot(path: Option<String>) -> Result<(), String> {
+    mpv_ipc::screenshot(path).await.map_err(map_err_frontend)
+}
+
+#[tauri::command]
+async fn player_get_property(name: String) -> Result<serde_json::Value, String> {
+    mpv_ipc::get_property(name).await.map_err(map_err_frontend)
+}
+
+#[tauri::command]
+async fn zap_next(
+    filters: Filters,
+    channel_id: i64,
+    state: State<'_, Mutex<AppState>>,
+    app: AppHandle,
+) -> Result<Channel, String> {
+    zapping::zap_next(filters, channel_id, state, app)
+        .await
+        .map_err(map_err_frontend)
+}
+
+#[tauri::command]
+async fn zap_previous(
+    filters: Filters,
+    channel_id: i64,
+    state: State<'_, Mutex<AppState>>,
+    app: AppHandle,
+) -> Result<Channel, String> {
+    zapping::zap_previous(filters, channel_id, state, app)
+        .await
+        .map_err(map_err_frontend)
+}
+
+#[tauri::command]
+async fn zap_to(
+    filters: Filters,
+    number: u32,
+    state: State<'_, Mutex<AppState>>,
+    app: AppHandle,
+) -> Result<Channel, String> {
+    zapping::zap_to(filters, number, state, app)
         .await
         .map_err(map_err_frontend)
 }
 
+#[tauri::command(async)]
+fn get_watch_progress(channel_id: i64) -> Result<Option<f64>, String> {
+    sql::get_watch_progress(channel_id).map_err(map_err_frontend)
+}
+
 #[tauri::command(async)]
 fn get_settings() -> Result<Settings, String> {
     settings::get_settings().map_err(map_err_frontend)
@@ -237,8 +414,190 @@ fn search(filters: Filters) -> Result<Vec<Channel>, String> {
 }
 
 #[tauri::command(async)]
-fn bulk_update(filters: Filters, action: u8) -> Result<(), String> {
-    sql::bulk_update(filters, action).map_err(map_err_frontend)
+fn search_page(filters: Filters) -> Result<SearchPage, String> {
+    sql::search_page(filters).map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn get_saved_searches() -> Result<Vec<SavedSearch>, String> {
+    sql::get_saved_searches().map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn save_search(search: SavedSearch) -> Result<(), String> {
+    sql::save_search(search).map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn delete_saved_search(id: i64) -> Result<(), String> {
+    sql::delete_saved_search(id).map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn share_saved_search(id: i64, path: String) -> Result<(), String> {
+    share::share_saved_search(id, path).map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn bulk_update(filters: Filters, action: u8, list_id: Option<i64>) -> Result<(), String> {
+    sql::bulk_update(filters, action, list_id).map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn get_lists() -> Result<Vec<UserList>, String> {
+    sql::get_lists().map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn save_list(list: UserList) -> Result<(), String> {
+    sql::save_list(list).map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn delete_list(id: i64) -> Result<(), String> {
+    sql::delete_list(id).map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn add_to_list(list_id: i64, channel_id: i64) -> Result<(), String> {
+    sql::add_to_list(list_id, channel_id).map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn remove_from_list(list_id: i64, channel_id: i64) -> Result<(), String> {
+    sql::remove_from_list(list_id, channel_id).map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn reorder_list(list_id: i64, channel_ids: Vec<i64>) -> Result<(), String> {
+    sql::reorder_list(list_id, channel_ids).map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn move_in_list(list_id: i64, channel_id: i64, position: usize) -> Result<(), String> {
+    sql::move_in_list(list_id, channel_id, position).map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn move_channel(id: i64, position: usize) -> Result<(), String> {
+    sql::move_channel(id, position).map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn reorder_channels(channel_ids: Vec<i64>) -> Result<(), String> {
+    sql::reorder_channels(channel_ids).map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn move_group(id: i64, position: usize) -> Result<(), String> {
+    sql::move_group(id, position).map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn reorder_groups(group_ids: Vec<i64>) -> Result<(), String> {
+    sql::reorder_groups(group_ids).map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn has_parental_pin() -> Result<bool, String> {
+    parental::has_pin().map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn set_parental_pin(current_pin: Option<String>, pin: Option<String>) -> Result<(), String> {
+    parental::set_pin(current_pin, pin).map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn parental_unlock(pin: String) -> Result<(), String> {
+    parental::unlock(pin).map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn parental_lock() -> Result<(), String> {
+    parental::lock().map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn is_parental_unlocked() -> Result<bool, String> {
+    parental::is_unlocked().map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn lock_channel(id: i64, locked: bool) -> Result<(), String> {
+    parental::ensure_unlocked()
+        .and_then(|_| sql::lock_channel(id, locked))
+        .map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn lock_group(id: i64, locked: bool) -> Result<(), String> {
+    parental::ensure_unlocked()
+        .and_then(|_| sql::lock_group(id, locked))
+        .map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn lock_source(id: i64, locked: bool) -> Result<(), String> {
+    parental::ensure_unlocked()
+        .and_then(|_| sql::lock_source(id, locked))
+        .map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn get_channel_playback_overrides(id: i64) -> Result<Option<PlaybackOverrides>, String> {
+    sql::get_channel_playback_overrides(id).map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn set_channel_playback_overrides(
+    id: i64,
+    overrides: Option<PlaybackOverrides>,
+) -> Result<(), String> {
+    sql::set_channel_playback_overrides(id, overrides).map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn get_source_playback_overrides(id: i64) -> Result<Option<PlaybackOverrides>, String> {
+    sql::get_source_playback_overrides(id).map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn set_source_playback_overrides(
+    id: i64,
+    overrides: Option<PlaybackOverrides>,
+) -> Result<(), String> {
+    sql::set_source_playback_overrides(id, overrides).map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn get_profiles() -> Result<Vec<Profile>, String> {
+    sql::get_profiles().map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn get_active_profile() -> Result<i64, String> {
+    sql::get_active_profile().map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn save_profile(profile: Profile) -> Result<(), String> {
+    sql::save_profile(profile).map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn delete_profile(id: i64) -> Result<(), String> {
+    sql::delete_profile(id).map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn switch_profile(id: i64) -> Result<(), String> {
+    sql::switch_profile(id).map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn get_channel_lists(channel_id: i64) -> Result<Vec<i64>, String> {
+    sql::get_channel_lists(channel_id).map_err(map_err_frontend)
 }
 
 #[tauri::command]
@@ -269,12 +628,12 @@ async fn get_episodes(channel: Channel) -> Result<(), String> {
 
 #[tauri::command(async)]
 fn favorite_channel(channel_id: i64) -> Result<(), String> {
-    sql::favorite_channel(channel_id, true).map_err(map_err_frontend)
+    logical::favorite_channel(channel_id, true).map_err(map_err_frontend)
 }
 
 #[tauri::command(async)]
 fn unfavorite_channel(channel_id: i64) -> Result<(), String> {
-    sql::favorite_channel(channel_id, false).map_err(map_err_frontend)
+    logical::favorite_channel(channel_id, false).map_err(map_err_frontend)
 }
 
 #[tauri::command(async)]
@@ -289,7 +648,100 @@ fn hide_group(id: i64, hidden: bool) -> Result<(), String> {
 
 #[tauri::command(async)]
 fn remove_from_history(id: i64) -> Result<(), String> {
-    sql::remove_last_watched(id).map_err(map_err_frontend)
+    logical::remove_last_watched(id).map_err(map_err_frontend)
+}
+
+#[tauri::command]
+async fn check_channels_health(
+    app: AppHandle,
+    state: State<'_, Mutex<AppState>>,
+    source_ids: Vec<i64>,
+    use_ffprobe: Option<bool>,
+) -> Result<(), String> {
+    health::check_health(app, state, source_ids, use_ffprobe.unwrap_or(false))
+        .await
+        .map_err(map_err_frontend)
+}
+
+#[tauri::command]
+async fn cancel_health_check(state: State<'_, Mutex<AppState>>) -> Result<(), String> {
+    health::cancel_health_check(state)
+        .await
+        .map_err(map_err_frontend)
+}
+
+#[tauri::command]
+async fn start_sleep_timer(
+    minutes: u32,
+    fade_out: bool,
+    state: State<'_, Mutex<AppState>>,
+    app: AppHandle,
+) -> Result<i64, String> {
+    sleep::start(minutes, fade_out, state, app)
+        .await
+        .map_err(map_err_frontend)
+}
+
+#[tauri::command]
+async fn start_sleep_timer_at_programme_end(
+    channel: Channel,
+    fade_out: bool,
+    state: State<'_, Mutex<AppState>>,
+    app: AppHandle,
+) -> Result<i64, String> {
+    sleep::start_at_programme_end(channel, fade_out, state, app)
+        .await
+        .map_err(map_err_frontend)
+}
+
+#[tauri::command]
+async fn get_sleep_timer(state: State<'_, Mutex<AppState>>) -> Result<Option<i64>, String> {
+    Ok(sleep::get(state).await)
+}
+
+#[tauri::command]
+async fn cancel_sleep_timer(state: State<'_, Mutex<AppState>>) -> Result<(), String> {
+    sleep::cancel(state).await.map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn credentials_locked() -> Result<bool, String> {
+    crypto::is_locked().map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn unlock_credentials(passphrase: String) -> Result<(), String> {
+    crypto::unlock(passphrase).map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn set_credentials_passphrase(passphrase: Option<String>) -> Result<(), String> {
+    crypto::set_passphrase(passphrase).map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn get_source_headers(source_id: i64) -> Result<Vec<HttpHeader>, String> {
+    sql::get_source_headers(source_id).map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn set_source_headers(source_id: i64, headers: Vec<HttpHeader>) -> Result<(), String> {
+    sql::do_tx(|tx| sql::set_source_headers(tx, source_id, headers)).map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn get_logical_channel_members(channel_id: i64) -> Result<Vec<Channel>, String> {
+    sql::get_logical_channel_members(channel_id).map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn get_name_rules(source_id: i64) -> Result<Vec<NameRule>, String> {
+    sql::do_tx(|tx| sql::get_name_rules(tx, source_id)).map_err(map_err_frontend)
+}
+
+#[tauri::command(async)]
+fn set_name_rules(source_id: i64, rules: Vec<NameRule>) -> Result<(), String> {
+    sql::do_tx(|tx| sql::set_name_rules(tx, source_id, rules)).map_err(map_err_frontend)
 }
 
 #[tauri::command(async)]
@@ -353,8 +805,12 @@ fn add_custom_source(name: String) -> Result<(), String> {
 }
 
 #[tauri::command(async)]
-fn share_custom_channel(channel: Channel, path: String) -> Result<(), String> {
-    share::share_custom_channel(channel, path).map_err(map_err_frontend)
+fn share_custom_channel(
+    channel: Channel,
+    path: String,
+    inline_headers: Option<bool>,
+) -> Result<(), String> {
+    share::share_custom_channel(channel, path, inline_headers).map_err(map_err_frontend)
 }
 
 #[tauri::command(async)]
@@ -396,13 +852,21 @@ fn group_exists(name: String, source_id: i64) -> Result<bool, String> {
 }
 
 #[tauri::command(async)]
-fn share_custom_group(group: Channel, path: String) -> Result<(), String> {
-    share::share_custom_group(group, path).map_err(map_err_frontend)
+fn share_custom_group(
+    group: Channel,
+    path: String,
+    inline_headers: Option<bool>,
+) -> Result<(), String> {
+    share::share_custom_group(group, path, inline_headers).map_err(map_err_frontend)
 }
 
 #[tauri::command(async)]
-fn share_custom_source(source: Source, path: String) -> Result<(), String> {
-    share::share_custom_source(source, path).map_err(map_err_frontend)
+fn share_custom_source(
+    source: Source,
+    path: String,
+    inline_headers: Option<bool>,
+) -> Result<(), String> {
+    share::share_custom_source(source, path, inline_headers).map_err(map_err_frontend)
 }
 
 #[tauri::command(async)]
@@ -510,8 +974,12 @@ async fn stop_restream(state: State<'_, Mutex<AppState>>) -> Result<(), String>
 }
 
 #[tauri::command]
-async fn watch_self(port: u16, state: State<'_, Mutex<AppState>>) -> Result<(), String> {
-    restream::watch_self(port, state)
+async fn watch_self(
+    port: u16,
+    state: State<'_, Mutex<AppState>>,
+    app: AppHandle,
+) -> Result<(), String> {
+    restream::watch_self(port, state, app)
         .await
         .map_err(map_err_frontend)
 }
@@ -528,7 +996,7 @@ fn share_restream(address: String, channel: Channel, path: String) -> Result<(),
 
 #[tauri::command(async)]
 fn add_last_watched(id: i64) -> Result<(), String> {
-    sql::add_last_watched(id).map_err(map_err_frontend)
+    logical::add_last_watched(id).map_err(map_err_frontend)
 }
 
 #[tauri::command(async)]
@@ -0,0 +1,153 @@
+use std::sync::LazyLock;
+
+use anyhow::{Context, Result};
+use regex::Regex;
+
+use crate::{
+    media_type, name_rules::normalize_for_matching, settings::get_settings, sql, types::Channel,
+};
+
+static UHD_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)\b(?:UHD|4K|8K|2160P)\b"#).unwrap());
+static FHD_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)\b(?:FHD|1080[PI])\b"#).unwrap());
+static SD_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"(?i)\b(?:SD|LQ)\b"#).unwrap());
+
+pub fn get_merge_key(channel: &Channel) -> Option<String> {
+    if channel.media_type != media_type::LIVESTREAM || channel.season_id.is_some() {
+        return None;
+    }
+    if let Some(tvg_id) = channel.tvg_id.as_ref().filter(|id| !id.trim().is_empty()) {
+        return Some(format!("id:{}", tvg_id.trim().to_lowercase()));
+    }
+    let name = normalize_for_matching(channel.original_name.as_ref().unwrap_or(&channel.name));
+    if name.is_empty() {
+        return None;
+    }
+    Some(format!("name:{name}"))
+}
+
+pub fn merge_enabled() -> bool {
+    get_settings()
+        .map(|s| s.merge_duplicates.unwrap_or(false))
+        .unwrap_or(false)
+}
+
+fn get_quality_rank(channel: &Channel) -> u8 {
+    let name = channel.original_name.as_ref().unwrap_or(&channel.name);
+    if UHD_REGEX.is_match(name) {
+        4
+    } else if FHD_REGEX.is_match(name) {
+        3
+    } else if SD_REGEX.is_match(name) {
+        1
+    } else {
+        2
+    }
+}
+
+pub fn get_stream_candidates(channel: Channel) -> Result<Vec<Channel>> {
+    if channel.media_type != media_type::LIVESTREAM || !merge_enabled() {
+        return Ok(vec![channel]);
+    }
+    let mut members = sql::get_logical_channel_members(channel.id.context("no channel id")?)?;
+    members.sort_by_key(|member| std::cmp::Reverse(get_quality_rank(member)));
+    let mut candidates = Vec::with_capacity(members.len() + 1);
+    candidates.push(channel);
+    candidates.append(&mut members);
+    Ok(candidates)
+}
+
+pub fn favorite_channel(channel_id: i64, favorite: bool) -> Result<()> {
+    if merge_enabled() {
+        sql::favorite_logical_channel(channel_id, favorite)
+    } else {
+        sql::favorite_channel(channel_id, favorite)
+    }
+}
+
+pub fn add_last_watched(channel_id: i64) -> Result<()> {
+    if merge_enabled() {
+        sql::remove_logical_last_watched(channel_id)?;
+    }
+    sql::add_last_watched(channel_id)
+}
+
+pub fn remove_last_watched(channel_id: i64) -> Result<()> {
+    if merge_enabled() {
+        sql::remove_logical_last_watched(channel_id)
+    } else {
+        sql::remove_last_watched(channel_id)
+    }
+}
+
+#[cfg(test)]
+mod test_logical {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{settings::MERGE_DUPLICATES, sql::test_db};
+
+    fn set_merge(enabled: Option<bool>) {
+        sql::update_settings(HashMap::from([(
+            MERGE_DUPLICATES.to_string(),
+            enabled.map(|e| e.to_string()),
+        )]))
+        .unwrap();
+    }
+
+    #[test]
+    fn test_merge_duplicates() {
+        let _lock = test_db::lock();
+        set_merge(Some(true));
+        let first = test_db::insert_source("merge first");
+        let second = test_db::insert_source("merge second");
+        let hd = test_db::insert_channel(first, "US: CNN HD");
+        test_db::insert_channel(second, "CNN SD");
+        test_db::insert_channel(second, "CNN 4K");
+        test_db::insert_channel(first, "CNN FHD");
+        test_db::insert_channel(first, "BBC");
+
+        let candidates: Vec<String> = get_stream_candidates(hd)
+            .unwrap()
+            .into_iter()
+            .map(|c| c.name)
+            .collect();
+        assert_eq!(candidates, ["US: CNN HD", "CNN 4K", "CNN FHD", "CNN SD"]);
+
+        let mut filters = test_db::filters(first);
+        filters.source_ids.push(second);
+        assert_eq!(test_db::names(filters.clone()), ["BBC", "US: CNN HD"]);
+        set_merge(None);
+        assert_eq!(test_db::names(filters).len(), 5);
+        sql::delete_source(first).unwrap();
+        sql::delete_source(second).unwrap();
+    }
+
+    #[test]
+    fn test_locked_candidates() {
+        let _lock = test_db::lock();
+        set_merge(Some(true));
+        let open = test_db::insert_source("candidates open");
+        let locked = test_db::insert_source("candidates locked");
+        let hd = test_db::insert_channel(open, "ESPN HD");
+        test_db::insert_channel(locked, "ESPN SD");
+        sql::lock_source(locked, true).unwrap();
+        crate::parental::set_pin(None, Some("1234".to_string())).unwrap();
+        let names = |channel: &Channel| -> Vec<String> {
+            get_stream_candidates(channel.clone())
+                .unwrap()
+                .into_iter()
+                .map(|c| c.name)
+                .collect()
+        };
+        assert_eq!(names(&hd), ["ESPN HD"]);
+        crate::parental::unlock("1234".to_string()).unwrap();
+        assert_eq!(names(&hd), ["ESPN HD", "ESPN SD"]);
+
+        crate::parental::set_pin(Some("1234".to_string()), None).unwrap();
+        set_merge(None);
+        sql::delete_source(open).unwrap();
+        sql::delete_source(locked).unwrap();
+    }
+}
@@ -11,12 +11,13 @@ use regex::{Captures, Regex};
 use rusqlite::Transaction;
 use types::{Channel, Source};
 
+use crate::name_rules::NameRewriter;
 use crate::types::ChannelPreserve;
 use crate::{
-    log, media_type, source_type,
+    log, media_type, parental, source_type,
     sql::{self, set_channel_group_id},
-    types::{self, ChannelHttpHeaders},
-    utils::get_user_agent_from_source,
+    types::{self, ChannelDrm, ChannelHttpHeaders, CustomChannel, HttpHeader},
+    utils::get_source_client,
 };
 
 static NAME_REGEX: LazyLock<Regex> =
@@ -36,16 +37,27 @@ static HTTP_REFERRER_REGEX: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r#"http-referrer=(?P<referrer>.+)"#).unwrap());
 static HTTP_USER_AGENT_REGEX: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r#"http-user-agent=(?P<user_agent>.+)"#).unwrap());
+static HTTP_COOKIE_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"http-cookie=(?P<cookie>.+)"#).unwrap());
+static KODIPROP_DRM_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"inputstream\.adaptive\.license_(?P<property>type|key)=(?P<value>.+)"#).unwrap()
+});
+static KODIPROP_HEADERS_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"inputstream\.adaptive\.(?:stream|common|manifest)_headers=(?P<headers>.+)"#)
+        .unwrap()
+});
 
 struct M3UProcessing {
     channel_line: Option<String>,
     channel_headers: Option<ChannelHttpHeaders>,
     channel_headers_set: bool,
+    channel_drm: Option<ChannelDrm>,
     last_non_empty_line: Option<String>,
     groups: HashMap<String, i64>,
     source_id: i64,
     use_tvg_id: Option<bool>,
     line_count: usize,
+    name_rewriter: NameRewriter,
 }
 
 pub fn read_m3u8(mut source: Source, wipe: bool) -> Result<()> {
@@ -66,15 +78,21 @@ pub fn read_m3u8(mut source: Source, wipe: bool) -> Result<()> {
     } else {
         source.id = Some(sql::create_or_find_source_by_name(&tx, &source)?);
     }
+    let name_rewriter = NameRewriter::new(sql::get_name_rules(
+        &tx,
+        source.id.context("no source id")?,
+    )?)?;
     let mut processing = M3UProcessing {
         channel_headers: None,
         channel_headers_set: false,
+        channel_drm: None,
         channel_line: None,
         groups: HashMap::new(),
         last_non_empty_line: None,
         source_id: source.id.context("no source id")?,
         use_tvg_id: source.use_tvg_id,
         line_count: 0,
+        name_rewriter,
     };
     while let Some((c1, l1)) = lines.next() {
         processing.line_count = c1;
@@ -90,16 +108,29 @@ pub fn read_m3u8(mut source: Source, wipe: bool) -> Result<()> {
             try_commit_channel(&mut processing, &tx);
             processing.channel_line = Some(l1);
             processing.channel_headers_set = false;
-        } else if l1_upper.starts_with("#EXTVLCOPT") {
+        } else if l1_upper.starts_with("#KODIPROP") && KODIPROP_DRM_REGEX.is_match(&l1) {
+            set_kodiprop_drm(
+                &l1,
+                processing.channel_drm.get_or_insert_with(Default::default),
+            );
+        } else if l1_upper.starts_with("#EXTVLCOPT")
+            || l1_upper.starts_with("#EXTHTTP")
+            || l1_upper.starts_with("#KODIPROP")
+        {
             if processing.channel_headers.is_none() {
                 processing.channel_headers = Some(ChannelHttpHeaders {
                     ..Default::default()
                 });
             }
-            if set_http_headers(
-                &l1,
-                processing.channel_headers.as_mut().context("no headers")?,
-            ) {
+            let headers = processing.channel_headers.as_mut().context("no headers")?;
+            let headers_set = if l1_upper.starts_with("#EXTHTTP") {
+                set_exthttp_headers(&l1, headers)
+            } else if l1_upper.starts_with("#KODIPROP") {
+                set_kodiprop_headers(&l1, headers)
+            } else {
+                set_http_headers(&l1, headers)
+            };
+            if headers_set {
                 processing.channel_headers_set = true;
             }
         } else if !l1.trim().is_empty() {
@@ -110,6 +141,7 @@ pub fn read_m3u8(mut source: Source, wipe: bool) -> Result<()> {
     if wipe {
         sql::restore_preserve(&tx, source.id.context("no source id")?, channel_preserve)?;
     }
+    parental::auto_lock(&tx, source.id.context("no source id")?)?;
     sql::analyze(&tx)?;
     tx.commit()?;
     Ok(())
@@ -123,10 +155,9 @@ fn try_commit_channel(processing: &mut M3UProcessing, tx: &Transaction) {
         commit_channel(
             channel,
             processing.last_non_empty_line.take(),
-            &mut processing.groups,
             processing.channel_headers.take(),
-            processing.source_id,
-            processing.use_tvg_id,
+            processing.channel_drm.take(),
+            processing,
             &tx,
         )
         .with_context(|| {
@@ -144,35 +175,46 @@ fn try_commit_channel(processing: &mut M3UProcessing, tx: &Transaction) {
 fn commit_channel(
     channel_line: String,
     last_line: Option<String>,
-    groups: &mut HashMap<String, i64>,
     headers: Option<ChannelHttpHeaders>,
-    source_id: i64,
-    use_tvg_id: Option<bool>,
+    drm: Option<ChannelDrm>,
+    processing: &mut M3UProcessing,
     tx: &Transaction,
 ) -> Result<()> {
+    let (url, headers) = split_url_headers(last_line.context("missing last line")?, headers);
     let mut channel = get_channel_from_lines(
         channel_line,
-        last_line.context("missing last line")?,
-        source_id,
-        use_tvg_id,
+        url,
+        processing.source_id,
+        processing.use_tvg_id,
+        &processing.name_rewriter,
     )?;
-    set_channel_group_id(groups, &mut channel, tx, &source_id).unwrap_or_else(|e| {
+    set_channel_group_id(
+        &mut processing.groups,
+        &mut channel,
+        tx,
+        &processing.source_id,
+    )
+    .unwrap_or_else(|e| {
         log::log(format!(
             "Failed to set group id for channel: {}, Error: {:?}",
             channel.name, e
         ))
     });
     sql::insert_channel(tx, channel)?;
+    let channel_id = tx.last_insert_rowid();
     if let Some(mut headers) = headers {
-        headers.channel_id = Some(tx.last_insert_rowid());
+        headers.channel_id = Some(channel_id);
         sql::insert_channel_headers(tx, headers)?;
     }
+    if let Some(mut drm) = drm {
+        drm.channel_id = Some(channel_id);
+        sql::insert_channel_drm(tx, drm)?;
+    }
     Ok(())
 }
 
 pub async fn get_m3u8_from_link(source: Source, wipe: bool) -> Result<()> {
-    let user_agent = get_user_agent_from_source(&source)?;
-    let client = reqwest::Client::builder().user_agent(user_agent).build()?;
+    let client = get_source_client(&source)?;
     let url = source.url.clone().context("Invalid source")?;
     let mut response = client.get(&url).send().await?;
     if !response.status().is_success() {
@@ -229,15 +271,152 @@ fn set_http_headers(line: &str, headers: &mut ChannelHttpHeaders) -> bool {
     {
         headers.user_agent = Some(user_agent);
         return true;
+    } else if let Some(cookie) = HTTP_COOKIE_REGEX
+        .captures(line)
+        .and_then(extract_non_empty_capture)
+    {
+        headers.cookies = Some(cookie);
+        return true;
     }
     return false;
 }
 
+fn set_exthttp_headers(line: &str, headers: &mut ChannelHttpHeaders) -> bool {
+    let json = match line.split_once(':') {
+        Some((_, json)) => json,
+        None => return false,
+    };
+    let map: serde_json::Map<String, serde_json::Value> = match serde_json::from_str(json) {
+        Ok(map) => map,
+        Err(e) => {
+            log::log(format!("Invalid #EXTHTTP line: {:?}", e));
+            return false;
+        }
+    };
+    let mut headers_set = false;
+    for (name, value) in map {
+        if let Some(value) = value.as_str().filter(|v| !v.trim().is_empty()) {
+            set_http_header(headers, &name, value);
+            headers_set = true;
+        }
+    }
+    headers_set
+}
+
+fn set_kodiprop_drm(line: &str, drm: &mut ChannelDrm) {
+    let Some(captures) = KODIPROP_DRM_REGEX.captures(line) else {
+        return;
+    };
+    let value = captures["value"].trim().to_string();
+    match &captures["property"] {
+        "type" => drm.license_type = Some(value),
+        _ => drm.license_key = Some(value),
+    }
+}
+
+fn set_kodiprop_headers(line: &str, headers: &mut ChannelHttpHeaders) -> bool {
+    let value = match KODIPROP_HEADERS_REGEX
+        .captures(line)
+        .and_then(extract_non_empty_capture)
+    {
+        Some(value) => value,
+        None => return false,
+    };
+    let mut headers_set = false;
+    for (name, value) in url::form_urlencoded::parse(value.trim().as_bytes()) {
+        if !value.trim().is_empty() {
+            set_http_header(headers, &name, &value);
+            headers_set = true;
+        }
+    }
+    headers_set
+}
+
+fn set_http_header(headers: &mut ChannelHttpHeaders, name: &str, value: &str) {
+    let name = name.trim();
+    let value = value.trim().to_string();
+    match name.to_lowercase().as_str() {
+        "user-agent" => headers.user_agent = Some(value),
+        "referer" | "referrer" => headers.referrer = Some(value),
+        "origin" => headers.http_origin = Some(value),
+        "cookie" => headers.cookies = Some(value),
+        _ => headers
+            .extra_headers
+            .get_or_insert_with(Vec::new)
+            .push(HttpHeader {
+                name: name.to_string(),
+                value,
+            }),
+    }
+}
+
+pub fn split_url_headers(
+    url: String,
+    headers: Option<ChannelHttpHeaders>,
+) -> (String, Option<ChannelHttpHeaders>) {
+    let Some((stream_url, suffix)) = url
+        .split_once('|')
+        .filter(|(_, suffix)| suffix.contains('='))
+    else {
+        return (url, headers);
+    };
+    let mut headers = headers.unwrap_or_default();
+    for (name, value) in url::form_urlencoded::parse(suffix.trim().as_bytes()) {
+        let value = value.trim().trim_matches('"');
+        if !name.trim().is_empty() && !value.is_empty() {
+            set_http_header(&mut headers, &name, value);
+        }
+    }
+    (stream_url.trim().to_string(), Some(headers))
+}
+
+pub fn split_custom_channel_url(channel: &mut CustomChannel) {
+    if let Some(url) = channel.data.url.take() {
+        let (url, headers) = split_url_headers(url, channel.headers.take());
+        channel.data.url = Some(url);
+        channel.headers = headers;
+    }
+}
+
+pub fn join_url_headers(
+    url: String,
+    headers: Option<ChannelHttpHeaders>,
+) -> (String, Option<ChannelHttpHeaders>) {
+    let Some(mut headers) = headers else {
+        return (url, None);
+    };
+    let mut pairs = url::form_urlencoded::Serializer::new(String::new());
+    if let Some(user_agent) = headers.user_agent.take() {
+        pairs.append_pair("User-Agent", &user_agent);
+    }
+    if let Some(referrer) = headers.referrer.take() {
+        pairs.append_pair("Referer", &referrer);
+    }
+    if let Some(origin) = headers.http_origin.take() {
+        pairs.append_pair("Origin", &origin);
+    }
+    if let Some(cookies) = headers.cookies.take() {
+        pairs.append_pair("Cookie", &cookies);
+    }
+    for header in headers.extra_headers.take().unwrap_or_default() {
+        pairs.append_pair(&header.name, &header.value);
+    }
+    let suffix = pairs.finish();
+    let url = if suffix.is_empty() {
+        url
+    } else {
+        format!("{url}|{suffix}")
+    };
+    let headers = (headers.ignore_ssl == Some(true)).then_some(headers);
+    (url, headers)
+}
+
 fn get_channel_from_lines(
     first: String,
     mut second: String,
     source_id: i64,
     use_tvg_id: Option<bool>,
+    name_rewriter: &NameRewriter,
 ) -> Result<Channel> {
     second = second.trim().to_string();
     if second.is_empty() {
@@ -264,15 +443,21 @@ fn get_channel_from_lines(
             }
         })
         .context("Couldn't find name from Name or ID")?;
+    let name = name.trim().to_string();
+    let rewritten_name = name_rewriter.rewrite(&name);
     let group = GROUP_REGEX
         .captures(&first)
         .and_then(extract_non_empty_capture);
     let image = LOGO_REGEX
         .captures(&first)
         .and_then(extract_non_empty_capture);
+    let tvg_id = ID_REGEX
+        .captures(&first)
+        .and_then(extract_non_empty_capture);
     let channel = Channel {
         id: None,
-        name: name.trim().to_string(),
+        original_name: (rewritten_name != name).then_some(name),
+        name: rewritten_name,
         group: group.map(|x| x.trim().to_string()),
         image: image.map(|x| x.trim().to_string()),
         url: Some(second.clone()),
@@ -286,6 +471,8 @@ fn get_channel_from_lines(
         season_id: None,
         episode_num: None,
         hidden: Some(false),
+        tvg_id: tvg_id.map(|x| x.trim().to_string()),
+        progress: None,
     };
     Ok(channel)
 }
@@ -305,23 +492,102 @@ mod test_m3u {
 
     use crate::{
         m3u::{get_channel_from_lines, get_m3u8_from_link},
+        name_rules::NameRewriter,
         types::Source,
     };
 
     use super::read_m3u8;
+    use super::{
+        join_url_headers, set_exthttp_headers, set_kodiprop_drm, set_kodiprop_headers,
+        split_url_headers,
+    };
+    use crate::types::{ChannelDrm, ChannelHttpHeaders, HttpHeader};
+
+    #[test]
+    fn test_set_headers() {
+        let mut headers = ChannelHttpHeaders::default();
+        assert!(set_exthttp_headers(
+            r#"#EXTHTTP:{"cookie":"token=abc","User-Agent":"Agent","X-Forwarded-For":"1.1.1.1"}"#,
+            &mut headers
+        ));
+        assert_eq!(headers.cookies.as_deref(), Some("token=abc"));
+        assert_eq!(headers.user_agent.as_deref(), Some("Agent"));
+        assert!(set_kodiprop_headers(
+            "#KODIPROP:inputstream.adaptive.stream_headers=Referer=https%3A%2F%2Fsite.local%2F&X-Token=1",
+            &mut headers
+        ));
+        assert_eq!(headers.referrer.as_deref(), Some("https://site.local/"));
+        assert_eq!(
+            headers.extra_headers,
+            Some(vec![
+                HttpHeader {
+                    name: "X-Forwarded-For".to_string(),
+                    value: "1.1.1.1".to_string()
+                },
+                HttpHeader {
+                    name: "X-Token".to_string(),
+                    value: "1".to_string()
+                }
+            ])
+        );
+        assert!(!set_exthttp_headers("#EXTHTTP:not json", &mut headers));
+    }
+
+    #[test]
+    fn test_set_drm() {
+        let mut drm = ChannelDrm::default();
+        set_kodiprop_drm(
+            "#KODIPROP:inputstream.adaptive.license_type=clearkey",
+            &mut drm,
+        );
+        set_kodiprop_drm(
+            "#KODIPROP:inputstream.adaptive.license_key=0123456789abcdef0123456789abcdef:fedcba9876543210fedcba9876543210",
+            &mut drm,
+        );
+        assert_eq!(drm.license_type.as_deref(), Some("clearkey"));
+        assert_eq!(
+            drm.license_key.as_deref(),
+            Some("0123456789abcdef0123456789abcdef:fedcba9876543210fedcba9876543210")
+        );
+    }
+
+    #[test]
+    fn test_url_headers() {
+        let (url, headers) = split_url_headers(
+            "http://myurl.local/1111/1111.m3u8|User-Agent=My Agent&Referer=https%3A%2F%2Fsite.local%2F&X-Token=1".to_string(),
+            None,
+        );
+        assert_eq!(url, "http://myurl.local/1111/1111.m3u8");
+        let headers = headers.unwrap();
+        assert_eq!(headers.user_agent.as_deref(), Some("My Agent"));
+        assert_eq!(headers.referrer.as_deref(), Some("https://site.local/"));
+        assert_eq!(
+            headers.extra_headers,
+            Some(vec![HttpHeader {
+                name: "X-Token".to_string(),
+                value: "1".to_string()
+            }])
+        );
+        let (joined, rest) = join_url_headers(url, Some(headers.clone()));
+        assert!(rest.is_none());
+        assert_eq!(split_url_headers(joined, None).1, Some(headers));
+        let (url, headers) = split_url_headers("http://myurl.local/a|b".to_string(), None);
+        assert_eq!(url, "http://myurl.local/a|b");
+        assert!(headers.is_none());
+    }
 
     #[test]
     fn test_get_channel_from_lines() {
         get_channel_from_lines(r#"#EXTINF:-1 tvg-id="Amazing Channel" tvg-name="Amazing Channel" tvg-logo="http://myurl.local/logos/amazing/amazing-1.png" group-title="The Best Channels"#.to_string()
-       , r#"http://myurl.local/1234/1234/1234"#.to_string(), 0,Some(true)).unwrap();
+       , r#"http://myurl.local/1234/1234/1234"#.to_string(), 0,Some(true), &NameRewriter::default()).unwrap();
         get_channel_from_lines(r#"#EXTINF:-1 tvg-id="Amazing Channel" tvg-name="" tvg-logo="http://myurl.local/logos/amazing/amazing-1.png" group-title="The Best Channels"#.to_string()
-       , r#"http://myurl.local/1234/1234/1234"#.to_string(), 0, Some(true)).unwrap();
+       , r#"http://myurl.local/1234/1234/1234"#.to_string(), 0, Some(true), &NameRewriter::default()).unwrap();
         assert!(get_channel_from_lines(r#"#EXTINF:-1 tvg-id="" tvg-name="" tvg-logo="http://myurl.local/logos/amazing/amazing-1.png" group-title="The Best Channels"#.to_string()
-       , r#"http://myurl.local/1234/1234/1234"#.to_string(), 0, Some(true)).is_err());
+       , r#"http://myurl.local/1234/1234/1234"#.to_string(), 0, Some(true), &NameRewriter::default()).is_err());
         assert!(get_channel_from_lines(r#"#EXTINF:-1 tvg-id=" " tvg-name="" tvg-logo="http://myurl.local/logos/amazing/amazing-1.png" group-title="The Best Channels"#.to_string()
-       , r#"http://myurl.local/1234/1234/1234"#.to_string(), 0, Some(true)).is_err());
-        assert!(get_channel_from_lines(r#"#EXTINF:-1 tvg-id="Id Of Channel" tvg-name="Name Of Channel" tvg-logo="http://myurl.local/amazing/stuff.png" group-title="|EU| FRANCE HEVC",Alt Name Of Channel"#.to_string(), "http://myurl.local/1111/1111.ts".to_string(), 0, Some(true)).unwrap().name == "Name Of Channel");
-        assert!(get_channel_from_lines(r#"#EXTINF:-1 tvg-id="Id Of Channel" tvg-name="" tvg-logo="http://myurl.local/amazing/stuff.png" group-title="|EU| FRANCE HEVC",Alt Name Of Channel"#.to_string(), "http://myurl.local/1111/1111.ts".to_string(), 0, Some(true)).unwrap().name == "Id Of Channel");
-        assert!(get_channel_from_lines(r#"#EXTINF:-1 tvg-id="Id Of Channel" tvg-name="" tvg-logo="http://myurl.local/amazing/stuff.png" group-title="|EU| FRANCE HEVC",Alt Name Of Channel"#.to_string(), "http://myurl.local/1111/1111.ts".to_string(), 0, Some(false)).unwrap().name == "Alt Name Of Channel");
+       , r#"http://myurl.local/1234/1234/1234"#.to_string(), 0, Some(true), &NameRewriter::default()).is_err());
+        assert!(get_channel_from_lines(r#"#EXTINF:-1 tvg-id="Id Of Channel" tvg-name="Name Of Channel" tvg-logo="http://myurl.local/amazing/stuff.png" group-title="|EU| FRANCE HEVC",Alt Name Of Channel"#.to_string(), "http://myurl.local/1111/1111.ts".to_string(), 0, Some(true), &NameRewriter::default()).unwrap().name == "Name Of Channel");
+        assert!(get_channel_from_lines(r#"#EXTINF:-1 tvg-id="Id Of Channel" tvg-name="" tvg-logo="http://myurl.local/amazing/stuff.png" group-title="|EU| FRANCE HEVC",Alt Name Of Channel"#.to_string(), "http://myurl.local/1111/1111.ts".to_string(), 0, Some(true), &NameRewriter::default()).unwrap().name == "Id Of Channel");
+        assert!(get_channel_from_lines(r#"#EXTINF:-1 tvg-id="Id Of Channel" tvg-name="" tvg-logo="http://myurl.local/amazing/stuff.png" group-title="|EU| FRANCE HEVC",Alt Name Of Channel"#.to_string(), "http://myurl.local/1111/1111.ts".to_string(), 0, Some(false), &NameRewriter::default()).unwrap().name == "Alt Name Of Channel");
     }
 }
@@ -13,6 +13,9 @@ fn main() -> Result<()> {
         .with_context(|| "Failed to delete db after nuke request")
         .inspect_err(|e| log::log(format!("{:?}", e)));
     open_tv_lib::sql::create_or_initialize_db()?;
+    _ = open_tv_lib::crypto::migrate_credentials()
+        .with_context(|| "Failed to encrypt stored credentials")
+        .inspect_err(|e| log::log(format!("{:?}", e)));
     open_tv_lib::run();
     Ok(())
 }
@@ -3,3 +3,5 @@ pub const MOVIE: u8 = 1;
 pub const SERIE: u8 = 2;
 pub const GROUP: u8 = 3;
 pub const SEASON: u8 = 4;
+pub const SAVED_SEARCH: u8 = 5;
+pub const LIST: u8 = 6;
@@ -0,0 +1,399 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, LazyLock, RwLock,
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde_json::{Value, json};
+use tauri::{AppHandle, Emitter};
+use tokio::{
+    io::{
+        AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, Lines, ReadHalf,
+        WriteHalf, split,
+    },
+    sync::{Mutex, oneshot},
+};
+
+use crate::{log, playback_error::PlaybackError, sql, types::PlayerEvent};
+
+pub const ARG_INPUT_IPC_SERVER: &str = "--input-ipc-server=";
+pub const PLAYER_EVENT: &str = "player_event";
+pub const EVENT_STARTED: &str = "started";
+pub const EVENT_EOF: &str = "eof";
+pub const EVENT_ERROR: &str = "error";
+pub const EVENT_STOPPED: &str = "stopped";
+pub const EVENT_RETRYING: &str = "retrying";
+const CONNECT_ATTEMPTS: u32 = 50;
+const CONNECT_DELAY: Duration = Duration::from_millis(100);
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+const OBSERVE_TIME_POS: u64 = 1;
+const OBSERVE_DURATION: u64 = 2;
+const OBSERVE_PLAYLIST_POS: u64 = 3;
+
+static IPC_COUNTER: AtomicUsize = AtomicUsize::new(0);
+static CONTROLLER: LazyLock<RwLock<Option<Arc<MpvController>>>> =
+    LazyLock::new(|| RwLock::new(None));
+
+trait IpcStream: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> IpcStream for T {}
+
+type IpcLines = Lines<BufReader<ReadHalf<Box<dyn IpcStream>>>>;
+
+pub struct MpvController {
+    writer: Mutex<WriteHalf<Box<dyn IpcStream>>>,
+    pending: std::sync::Mutex<HashMap<u64, oneshot::Sender<Value>>>,
+    next_request: AtomicU64,
+    playlist: std::sync::Mutex<Vec<i64>>,
+    progress: std::sync::Mutex<Progress>,
+    track_progress: AtomicBool,
+}
+
+pub fn get_ipc_path() -> String {
+    let name = format!(
+        "open-tv-mpv-{}-{}",
+        std::process::id(),
+        IPC_COUNTER.fetch_add(1, Ordering::Relaxed)
+    );
+    #[cfg(windows)]
+    return format!(r"\\.\pipe\{name}");
+    #[cfg(not(windows))]
+    return std::env::temp_dir()
+        .join(format!("{name}.sock"))
+        .to_string_lossy()
+        .to_string();
+}
+
+#[cfg(not(windows))]
+async fn open_stream(path: &str) -> std::io::Result<Box<dyn IpcStream>> {
+    Ok(Box::new(tokio::net::UnixStream::connect(path).await?))
+}
+
+#[cfg(windows)]
+async fn open_stream(path: &str) -> std::io::Result<Box<dyn IpcStream>> {
+    Ok(Box::new(
+        tokio::net::windows::named_pipe::ClientOptions::new().open(path)?,
+    ))
+}
+
+async fn connect(path: &str) -> Result<(IpcLines, WriteHalf<Box<dyn IpcStream>>)> {
+    for _ in 0..CONNECT_ATTEMPTS {
+        if let Ok(stream) = open_stream(path).await {
+            let (reader, writer) = split(stream);
+            return Ok((BufReader::new(reader).lines(), writer));
+        }
+        tokio::time::sleep(CONNECT_DELAY).await;
+    }
+    bail!("Failed to connect to mpv IPC at {path}")
+}
+
+fn lock_poisoned<T>(_: T) -> anyhow::Error {
+    anyhow!("mpv controller lock poisoned")
+}
+
+fn get_controller() -> Result<Arc<MpvController>> {
+    CONTROLLER
+        .read()
+        .map_err(lock_poisoned)?
+        .clone()
+        .context("No player is running")
+}
+
+fn set_controller(controller: Option<Arc<MpvController>>) -> Result<()> {
+    *CONTROLLER.write().map_err(lock_poisoned)? = controller;
+    Ok(())
+}
+
+fn clear_controller(controller: &Arc<MpvController>) -> Result<()> {
+    let mut current = CONTROLLER.write().map_err(lock_poisoned)?;
+    if current.as_ref().is_some_and(|c| Arc::ptr_eq(c, controller)) {
+        *current = None;
+    }
+    Ok(())
+}
+
+impl MpvController {
+    async fn write(&self, message: Value) -> Result<()> {
+        let mut line = serde_json::to_string(&message)?;
+        line.push('\n');
+        self.writer.lock().await.write_all(line.as_bytes()).await?;
+        Ok(())
+    }
+
+    async fn request(&self, command: Value) -> Result<Value> {
+        let request_id = self.next_request.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        self.pending
+            .lock()
+            .map_err(lock_poisoned)?
+            .insert(request_id, tx);
+        self.write(json!({ "command": command, "request_id": request_id }))
+            .await?;
+        let response = tokio::time::timeout(COMMAND_TIMEOUT, rx)
+            .await
+            .context("mpv did not answer in time")?
+            .context("mpv closed before answering")?;
+        match response.get("error").and_then(Value::as_str) {
+            Some("success") => Ok(response.get("data").cloned().unwrap_or(Value::Null)),
+            error => bail!("mpv command failed: {}", error.unwrap_or("unknown error")),
+        }
+    }
+
+    fn resolve(&self, message: &Value) -> Result<()> {
+        if let Some(request_id) = message.get("request_id").and_then(Value::as_u64)
+            && let Some(tx) = self
+                .pending
+                .lock()
+                .map_err(lock_poisoned)?
+                .remove(&request_id)
+        {
+            _ = tx.send(message.clone());
+        }
+        Ok(())
+    }
+
+    fn get_channel_id(&self, index: usize) -> Option<i64> {
+        self.playlist.lock().ok()?.get(index).copied()
+    }
+
+    fn get_current_channel_id(&self) -> Option<i64> {
+        self.get_channel_id(self.progress.lock().ok()?.index)
+    }
+
+    fn save_progress(&self) -> Result<()> {
+        if !self.track_progress.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let progress = self.progress.lock().map_err(lock_poisoned)?;
+        let (Some(position), Some(duration), Some(channel_id)) = (
+            progress.position,
+            progress.duration,
+            self.get_channel_id(progress.index),
+        ) else {
+            return Ok(());
+        };
+        _ = sql::save_watch_progress(channel_id, position, duration)
+            .map_err(|e| log::log(format!("Failed to save watch progress: {:?}", e)));
+        Ok(())
+    }
+
+    fn update_progress(&self, id: Option<u64>, data: Option<&Value>) -> Result<()> {
+        if let Some(OBSERVE_PLAYLIST_POS) = id
+            && let Some(index) = data.and_then(Value::as_u64).map(|i| i as usize)
+            && index != self.progress.lock().map_err(lock_poisoned)?.index
+        {
+            self.save_progress()?;
+            *self.progress.lock().map_err(lock_poisoned)? = Progress {
+                index,
+                ..Default::default()
+            };
+            return Ok(());
+        }
+        let mut progress = self.progress.lock().map_err(lock_poisoned)?;
+        match id {
+            Some(OBSERVE_TIME_POS) => {
+                progress.position = data.and_then(Value::as_f64).or(progress.position)
+            }
+            Some(OBSERVE_DURATION) => {
+                progress.duration = data.and_then(Value::as_f64).or(progress.duration)
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn replace_playlist(&self, playlist: Vec<i64>, track_progress: bool) -> Result<()> {
+        self.save_progress()?;
+        *self.progress.lock().map_err(lock_poisoned)? = Progress::default();
+        *self.playlist.lock().map_err(lock_poisoned)? = playlist;
+        self.track_progress.store(track_progress, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Progress {
+    index: usize,
+    position: Option<f64>,
+    duration: Option<f64>,
+}
+
+pub fn emit(app: &AppHandle, kind: &str, channel_id: Option<i64>, message: Option<String>) {
+    let _ = app.emit(
+        PLAYER_EVENT,
+        PlayerEvent {
+            kind: kind.to_string(),
+            channel_id,
+            message,
+            error_kind: None,
+        },
+    );
+}
+
+pub fn emit_error(app: &AppHandle, channel_id: Option<i64>, error: &PlaybackError) {
+    let _ = app.emit(
+        PLAYER_EVENT,
+        PlayerEvent {
+            kind: EVENT_ERROR.to_string(),
+            channel_id,
+            message: Some(error.message.clone()),
+            error_kind: Some(error.kind),
+        },
+    );
+}
+
+/// Attaches to the mpv instance listening on `path` and makes it the target of
+/// player commands until it exits. Watch progress is saved when `track_progress` is set.
+pub async fn run_session(
+    path: String,
+    playlist: Vec<i64>,
+    track_progress: bool,
+    app: AppHandle,
+) -> Result<Option<i64>> {
+    let (mut lines, writer) = connect(&path).await?;
+    let controller = Arc::new(MpvController {
+        writer: Mutex::new(writer),
+        pending: std::sync::Mutex::new(HashMap::new()),
+        next_request: AtomicU64::new(1),
+        playlist: std::sync::Mutex::new(playlist),
+        progress: std::sync::Mutex::new(Progress::default()),
+        track_progress: AtomicBool::new(track_progress),
+    });
+    set_controller(Some(controller.clone()))?;
+    for (id, property) in [
+        (OBSERVE_TIME_POS, "time-pos"),
+        (OBSERVE_DURATION, "duration"),
+        (OBSERVE_PLAYLIST_POS, "playlist-pos"),
+    ] {
+        controller
+            .write(json!({ "command": ["observe_property", id, property] }))
+            .await?;
+    }
+    let mut last_save = Instant::now();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let Ok(message) = serde_json::from_str::<Value>(&line) else {
+            continue;
+        };
+        controller.resolve(&message)?;
+        let channel_id = controller.get_current_channel_id();
+        match message.get("event").and_then(Value::as_str) {
+            Some("file-loaded") => emit(&app, EVENT_STARTED, channel_id, None),
+            Some("end-file") => match message.get("reason").and_then(Value::as_str) {
+                Some("eof") => emit(&app, EVENT_EOF, channel_id, None),
+                Some("error") => emit(
+                    &app,
+                    EVENT_ERROR,
+                    channel_id,
+                    message
+                        .get("file_error")
+                        .and_then(Value::as_str)
+                        .map(|e| e.to_string()),
+                ),
+                _ => {}
+            },
+            Some("property-change") => controller.update_progress(
+                message.get("id").and_then(Value::as_u64),
+                message.get("data"),
+            )?,
+            _ => {}
+        }
+        if last_save.elapsed() >= SAVE_INTERVAL {
+            controller.save_progress()?;
+            last_save = Instant::now();
+        }
+    }
+    controller.save_progress()?;
+    clear_controller(&controller)?;
+    let channel_id = controller.get_current_channel_id();
+    emit(&app, EVENT_STOPPED, channel_id, None);
+    Ok(channel_id)
+}
+
+pub fn get_current_channel_id() -> Option<i64> {
+    get_controller().ok()?.get_current_channel_id()
+}
+
+/// Replaces what the running mpv plays without restarting it. Stream options that
+/// were passed on the command line at launch are updated through properties first.
+pub async fn load(
+    url: String,
+    playlist: Vec<i64>,
+    track_progress: bool,
+    properties: Vec<(String, Value)>,
+    params: Vec<(String, Value)>,
+) -> Result<()> {
+    let controller = get_controller()?;
+    for (name, value) in properties {
+        controller
+            .request(json!(["set_property", name, value]))
+            .await?;
+    }
+    // Extra mpv arguments may not exist as properties, those only apply on launch
+    for (name, value) in params {
+        if let Err(e) = controller
+            .request(json!(["set_property", name, value]))
+            .await
+        {
+            log::log(format!("Could not set {name} while zapping: {:?}", e));
+        }
+    }
+    controller.replace_playlist(playlist, track_progress)?;
+    controller
+        .request(json!(["loadfile", url, "replace"]))
+        .await?;
+    Ok(())
+}
+
+pub async fn command(command: Value) -> Result<Value> {
+    get_controller()?.request(command).await
+}
+
+pub async fn pause(paused: Option<bool>) -> Result<()> {
+    match paused {
+        Some(paused) => command(json!(["set_property", "pause", paused])).await?,
+        None => command(json!(["cycle", "pause"])).await?,
+    };
+    Ok(())
+}
+
+pub async fn seek(seconds: f64, absolute: bool) -> Result<()> {
+    let mode = match absolute {
+        true => "absolute",
+        false => "relative",
+    };
+    command(json!(["seek", seconds, mode])).await?;
+    Ok(())
+}
+
+pub async fn set_volume(volume: u8) -> Result<()> {
+    command(json!(["set_property", "volume", volume])).await?;
+    Ok(())
+}
+
+pub async fn set_audio_track(track_id: Option<i64>) -> Result<()> {
+    let track = track_id.map_or(json!("no"), |id| json!(id));
+    command(json!(["set_property", "aid", track])).await?;
+    Ok(())
+}
+
+pub async fn set_subtitle_track(track_id: Option<i64>) -> Result<()> {
+    let track = track_id.map_or(json!("no"), |id| json!(id));
+    command(json!(["set_property", "sid", track])).await?;
+    Ok(())
+}
+
+pub async fn screenshot(path: Option<String>) -> Result<()> {
+    match path {
+        Some(path) => command(json!(["screenshot-to-file", path])).await?,
+        None => command(json!(["screenshot"])).await?,
+    };
+    Ok(())
+}
+
+pub async fn get_property(name: String) -> Result<Value> {
+    command(json!(["get_property", name])).await
+}
@@ -0,0 +1,4 @@
+pub const REGEX_REPLACE: u8 = 0;
+pub const STRIP_COUNTRY_PREFIX: u8 = 1;
+pub const STRIP_QUALITY_TAGS: u8 = 2;
+pub const NORMALIZE_UNICODE: u8 = 3;
@@ -0,0 +1,256 @@
+use std::sync::LazyLock;
+
+use anyhow::{Context, Result, bail};
+use regex::Regex;
+
+use crate::{name_rule_type, types::NameRule};
+
+static COUNTRY_PREFIX_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"^(?:\s*(?:\|[^|]{1,8}\||\[[A-Za-z]{2,4}\]|[A-Z]{2,3}\s*[:|]|[A-Z]{2,3}\s+-\s+))+"#,
+    )
+    .unwrap()
+});
+static QUALITY_TAGS_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)[\[(]?\b(?:UHD|FHD|HD|SD|HQ|LQ|4K|8K|HEVC|H\.?26[45]|X26[45]|1080[PI]|720P|2160P|HDR10|HDR|\d{2}FPS)\b[\])]?"#)
+        .unwrap()
+});
+static SUPERSCRIPT_TAGS_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"[\x{1D2C}-\x{1D6A}\x{1D9C}-\x{1DBF}\x{2070}-\x{209C}]+"#).unwrap()
+});
+static WHITESPACE_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"\s+"#).unwrap());
+static TRAILING_SEPARATORS_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"^[\s\-|:]+|[\s\-|:]+$"#).unwrap());
+
+enum CompiledRule {
+    RegexReplace(Regex, String),
+    StripCountryPrefix,
+    StripQualityTags,
+    NormalizeUnicode,
+}
+
+#[derive(Default)]
+pub struct NameRewriter {
+    rules: Vec<CompiledRule>,
+}
+
+impl NameRewriter {
+    pub fn new(rules: Vec<NameRule>) -> Result<NameRewriter> {
+        let rules = rules
+            .into_iter()
+            .map(compile_rule)
+            .collect::<Result<Vec<CompiledRule>>>()?;
+        Ok(NameRewriter { rules })
+    }
+
+    pub fn rewrite(&self, name: &str) -> String {
+        if self.rules.is_empty() {
+            return name.to_string();
+        }
+        let mut result = name.to_string();
+        for rule in &self.rules {
+            result = match rule {
+                CompiledRule::RegexReplace(regex, replacement) => {
+                    regex.replace_all(&result, replacement.as_str()).to_string()
+                }
+                CompiledRule::StripCountryPrefix => {
+                    COUNTRY_PREFIX_REGEX.replace(&result, "").to_string()
+                }
+                CompiledRule::StripQualityTags => {
+                    let result = SUPERSCRIPT_TAGS_REGEX.replace_all(&result, "");
+                    QUALITY_TAGS_REGEX.replace_all(&result, "").to_string()
+                }
+                CompiledRule::NormalizeUnicode => result.chars().map(normalize_char).collect(),
+            };
+        }
+        let result = WHITESPACE_REGEX.replace_all(&result, " ");
+        let result = TRAILING_SEPARATORS_REGEX.replace_all(&result, "");
+        if result.is_empty() {
+            return name.to_string();
+        }
+        result.to_string()
+    }
+}
+
+pub fn normalize_for_matching(name: &str) -> String {
+    let name = SUPERSCRIPT_TAGS_REGEX.replace_all(name, "");
+    let name: String = name.chars().map(normalize_char).collect();
+    let name = COUNTRY_PREFIX_REGEX.replace(&name, "");
+    let name = QUALITY_TAGS_REGEX.replace_all(&name, "");
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+/// Plain lowercase words for search, stylized letters and diacritics are folded away
+pub fn fold_for_search(value: &str) -> String {
+    value
+        .chars()
+        .map(normalize_char)
+        .flat_map(|c| c.to_lowercase())
+        .map(|c| {
+            if c.is_alphanumeric() {
+                strip_diacritic(c)
+            } else {
+                ' '
+            }
+        })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn compile_rule(rule: NameRule) -> Result<CompiledRule> {
+    match rule.rule_type {
+        name_rule_type::REGEX_REPLACE => {
+            let pattern = rule.pattern.context("regex rule has no pattern")?;
+            let regex = Regex::new(&pattern)
+                .with_context(|| format!("Invalid regex in name rule: {pattern}"))?;
+            Ok(CompiledRule::RegexReplace(
+                regex,
+                rule.replacement.unwrap_or_default(),
+            ))
+        }
+        name_rule_type::STRIP_COUNTRY_PREFIX => Ok(CompiledRule::StripCountryPrefix),
+        name_rule_type::STRIP_QUALITY_TAGS => Ok(CompiledRule::StripQualityTags),
+        name_rule_type::NORMALIZE_UNICODE => Ok(CompiledRule::NormalizeUnicode),
+        _ => bail!("Invalid name rule type {}", rule.rule_type),
+    }
+}
+
+fn normalize_char(c: char) -> char {
+    match c {
+        'ᴬ' | 'ᴀ' => 'A',
+        'ᴮ' | 'ʙ' => 'B',
+        'ᶜ' | 'ᴄ' => 'C',
+        'ᴰ' | 'ᴅ' => 'D',
+        'ᴱ' | 'ᴇ' => 'E',
+        'ᶠ' | 'ꜰ' => 'F',
+        'ᴳ' | 'ɢ' => 'G',
+        'ᴴ' | 'ʜ' => 'H',
+        'ᴵ' | 'ɪ' => 'I',
+        'ᴶ' | 'ᴊ' => 'J',
+        'ᴷ' | 'ᴋ' => 'K',
+        'ᴸ' | 'ʟ' => 'L',
+        'ᴹ' | 'ᴍ' => 'M',
+        'ᴺ' | 'ɴ' => 'N',
+        'ᴼ' | 'ᴏ' => 'O',
+        'ᴾ' | 'ᴘ' => 'P',
+        'ᴿ' | 'ʀ' => 'R',
+        'ˢ' | 'ꜱ' => 'S',
+        'ᵀ' | 'ᴛ' => 'T',
+        'ᵁ' | 'ᴜ' => 'U',
+        'ⱽ' | 'ᴠ' => 'V',
+        'ᵂ' | 'ᴡ' => 'W',
+        'ʏ' => 'Y',
+        'ᴢ' => 'Z',
+        'ᵃ' => 'a',
+        'ᵇ' => 'b',
+        'ᵈ' => 'd',
+        'ᵉ' => 'e',
+        'ᵍ' => 'g',
+        'ʰ' => 'h',
+        'ⁱ' => 'i',
+        'ʲ' => 'j',
+        'ᵏ' => 'k',
+        'ˡ' => 'l',
+        'ᵐ' => 'm',
+        'ⁿ' => 'n',
+        'ᵒ' => 'o',
+        'ᵖ' => 'p',
+        'ʳ' => 'r',
+        'ᵗ' => 't',
+        'ᵘ' => 'u',
+        'ᵛ' => 'v',
+        'ʷ' => 'w',
+        'ˣ' => 'x',
+        'ʸ' => 'y',
+        'ᶻ' => 'z',
+        '⁰' | '₀' => '0',
+        '¹' | '₁' => '1',
+        '²' | '₂' => '2',
+        '³' | '₃' => '3',
+        '⁴' | '₄' => '4',
+        '⁵' | '₅' => '5',
+        '⁶' | '₆' => '6',
+        '⁷' | '₇' => '7',
+        '⁸' | '₈' => '8',
+        '⁹' | '₉' => '9',
+        '⁺' | '₊' => '+',
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+        _ => c,
+    }
+}
+
+fn strip_diacritic(c: char) -> char {
+    match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => 'a',
+        'ç' | 'ć' | 'č' => 'c',
+        'ď' => 'd',
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ė' | 'ę' | 'ě' => 'e',
+        'ğ' => 'g',
+        'ì' | 'í' | 'î' | 'ï' | 'ī' | 'į' | 'ı' => 'i',
+        'ł' | 'ľ' => 'l',
+        'ñ' | 'ń' | 'ň' => 'n',
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ő' => 'o',
+        'ř' => 'r',
+        'ś' | 'š' | 'ş' => 's',
+        'ť' | 'ţ' => 't',
+        'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' | 'ű' => 'u',
+        'ý' | 'ÿ' => 'y',
+        'ź' | 'ż' | 'ž' => 'z',
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod test_name_rules {
+    use super::{NameRewriter, normalize_for_matching};
+    use crate::{name_rule_type, types::NameRule};
+
+    fn rule(rule_type: u8, pattern: Option<&str>, replacement: Option<&str>) -> NameRule {
+        NameRule {
+            id: None,
+            source_id: None,
+            rule_type,
+            pattern: pattern.map(|s| s.to_string()),
+            replacement: replacement.map(|s| s.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_rewrite() {
+        let rewriter = NameRewriter::new(vec![
+            rule(name_rule_type::STRIP_COUNTRY_PREFIX, None, None),
+            rule(name_rule_type::STRIP_QUALITY_TAGS, None, None),
+        ])
+        .unwrap();
+        assert_eq!(rewriter.rewrite("|EU| FR: TF1 FHD ᴿᴬᵂ"), "TF1");
+        assert_eq!(rewriter.rewrite("FR - France 2 (HD)"), "France 2");
+        assert_eq!(rewriter.rewrite("WWE RAW HEVC"), "WWE RAW");
+        let rewriter =
+            NameRewriter::new(vec![rule(name_rule_type::NORMALIZE_UNICODE, None, None)]).unwrap();
+        assert_eq!(rewriter.rewrite("TF1 ᴿᴬᵂ"), "TF1 RAW");
+        let rewriter = NameRewriter::new(vec![rule(
+            name_rule_type::REGEX_REPLACE,
+            Some(r"^VIP\s*"),
+            None,
+        )])
+        .unwrap();
+        assert_eq!(rewriter.rewrite("VIP CNN"), "CNN");
+        assert_eq!(rewriter.rewrite("VIP"), "VIP");
+        assert!(
+            NameRewriter::new(vec![rule(name_rule_type::REGEX_REPLACE, Some("("), None)]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_normalize_for_matching() {
+        assert_eq!(normalize_for_matching("US: CNN HD"), "cnn");
+        assert_eq!(normalize_for_matching("|US| CNN (FHD)"), "cnn");
+        assert_eq!(normalize_for_matching("CNN 4K ᴿᴬᵂ"), "cnn");
+        assert_eq!(normalize_for_matching("Canal+ Sport"), "canalsport");
+    }
+}
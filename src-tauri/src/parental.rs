@@ -0,0 +1,250 @@
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use argon2::Argon2;
+use base64::{Engine, prelude::BASE64_STANDARD};
+use chacha20poly1305::aead::{OsRng, rand_core::RngCore};
+use rusqlite::Transaction;
+
+use crate::{settings::get_settings, sql, types::Channel};
+
+const PIN_HASH: &str = "parentalPinHash";
+const PIN_SALT: &str = "parentalPinSalt";
+const SALT_SIZE: usize = 16;
+const HASH_SIZE: usize = 32;
+const DEFAULT_UNLOCK_MINUTES: u16 = 15;
+const MAX_PIN_ATTEMPTS: u8 = 5;
+const PIN_LOCKOUT: Duration = Duration::from_secs(5 * 60);
+pub const DEFAULT_KEYWORDS: &str = "adult,xxx,18+,porn,erotic";
+
+static UNLOCKED_UNTIL: LazyLock<RwLock<Option<Instant>>> = LazyLock::new(|| RwLock::new(None));
+static PIN_ATTEMPTS: Mutex<PinAttempts> = Mutex::new(PinAttempts {
+    failures: 0,
+    locked_until: None,
+});
+
+struct PinAttempts {
+    failures: u8,
+    locked_until: Option<Instant>,
+}
+
+fn set_unlocked_until(until: Option<Instant>) -> Result<()> {
+    *UNLOCKED_UNTIL
+        .write()
+        .map_err(|_| anyhow!("parental lock poisoned"))? = until;
+    Ok(())
+}
+
+fn hash_pin(pin: &str, salt: &[u8]) -> Result<[u8; HASH_SIZE]> {
+    let mut hash = [0u8; HASH_SIZE];
+    Argon2::default()
+        .hash_password_into(pin.as_bytes(), salt, &mut hash)
+        .map_err(|e| anyhow!("Failed to hash PIN: {e}"))?;
+    Ok(hash)
+}
+
+pub fn has_pin() -> Result<bool> {
+    Ok(sql::get_settings()?.contains_key(PIN_HASH))
+}
+
+fn verify_pin(pin: &str) -> Result<bool> {
+    let settings = sql::get_settings()?;
+    let Some(hash) = settings.get(PIN_HASH) else {
+        return Ok(true);
+    };
+    let salt = BASE64_STANDARD.decode(settings.get(PIN_SALT).context("Missing PIN salt")?)?;
+    Ok(BASE64_STANDARD.encode(hash_pin(pin, &salt)?) == *hash)
+}
+
+fn check_pin(pin: &str) -> Result<()> {
+    check_pin_at(pin, Instant::now())
+}
+
+/// Refuses any PIN for a while after too many wrong ones in a row
+fn check_pin_at(pin: &str, now: Instant) -> Result<()> {
+    // Held while hashing so attempts can't run in parallel
+    let mut attempts = PIN_ATTEMPTS
+        .lock()
+        .map_err(|_| anyhow!("parental lock poisoned"))?;
+    if let Some(until) = attempts.locked_until {
+        if now < until {
+            let minutes = (until - now).as_secs().div_ceil(60);
+            bail!("Too many invalid PINs, try again in {minutes} minute(s)");
+        }
+        attempts.locked_until = None;
+    }
+    if verify_pin(pin)? {
+        attempts.failures = 0;
+        return Ok(());
+    }
+    attempts.failures += 1;
+    if attempts.failures >= MAX_PIN_ATTEMPTS {
+        attempts.failures = 0;
+        attempts.locked_until = Some(now + PIN_LOCKOUT);
+    }
+    bail!("Invalid PIN")
+}
+
+pub fn set_pin(current_pin: Option<String>, pin: Option<String>) -> Result<()> {
+    if has_pin()? {
+        check_pin(current_pin.as_deref().unwrap_or_default())?;
+    }
+    let mut settings: HashMap<String, Option<String>> = HashMap::new();
+    match pin {
+        Some(pin) => {
+            if pin.trim().is_empty() {
+                bail!("PIN can't be empty");
+            }
+            let mut salt = [0u8; SALT_SIZE];
+            OsRng.fill_bytes(&mut salt);
+            settings.insert(
+                PIN_HASH.to_string(),
+                Some(BASE64_STANDARD.encode(hash_pin(&pin, &salt)?)),
+            );
+            settings.insert(PIN_SALT.to_string(), Some(BASE64_STANDARD.encode(salt)));
+        }
+        None => {
+            settings.insert(PIN_HASH.to_string(), None);
+            settings.insert(PIN_SALT.to_string(), None);
+        }
+    }
+    sql::update_settings(settings)?;
+    set_unlocked_until(None)
+}
+
+pub fn unlock(pin: String) -> Result<()> {
+    check_pin(&pin)?;
+    let minutes = get_settings()?
+        .parental_unlock_minutes
+        .unwrap_or(DEFAULT_UNLOCK_MINUTES);
+    set_unlocked_until(Some(
+        Instant::now() + Duration::from_secs(minutes as u64 * 60),
+    ))
+}
+
+pub fn lock() -> Result<()> {
+    set_unlocked_until(None)
+}
+
+pub fn is_unlocked() -> Result<bool> {
+    is_unlocked_at(Instant::now())
+}
+
+fn is_unlocked_at(now: Instant) -> Result<bool> {
+    let until = *UNLOCKED_UNTIL
+        .read()
+        .map_err(|_| anyhow!("parental lock poisoned"))?;
+    if until.is_some_and(|until| now < until) {
+        return Ok(true);
+    }
+    Ok(!has_pin()?)
+}
+
+pub fn ensure_unlocked() -> Result<()> {
+    if !is_unlocked()? {
+        bail!("Parental lock is active, please enter your PIN");
+    }
+    Ok(())
+}
+
+pub fn ensure_can_play(channel: &Channel) -> Result<()> {
+    if let Some(id) = channel.id
+        && sql::is_channel_locked(id)?
+    {
+        ensure_unlocked()?;
+    }
+    Ok(())
+}
+
+pub fn auto_lock(tx: &Transaction, source_id: i64) -> Result<()> {
+    let settings = get_settings()?;
+    if !settings.parental_auto_lock.unwrap_or(false) {
+        return Ok(());
+    }
+    let keywords = settings
+        .parental_keywords
+        .unwrap_or_else(|| DEFAULT_KEYWORDS.to_string());
+    let keywords: Vec<&str> = keywords
+        .split(',')
+        .map(|k| k.trim())
+        .filter(|k| !k.is_empty())
+        .collect();
+    sql::lock_groups_by_keywords(tx, source_id, &keywords)
+}
+
+#[cfg(test)]
+mod test_parental {
+    use super::*;
+    use crate::sql::test_db;
+
+    #[test]
+    fn test_pin() {
+        let _lock = test_db::lock();
+        assert!(!has_pin().unwrap());
+        assert!(is_unlocked().unwrap());
+        set_pin(None, Some("1234".to_string())).unwrap();
+        assert!(has_pin().unwrap());
+        assert!(verify_pin("1234").unwrap());
+        assert!(!verify_pin("4321").unwrap());
+        assert!(!is_unlocked().unwrap());
+
+        assert!(set_pin(Some("0000".to_string()), Some("5678".to_string())).is_err());
+        set_pin(Some("1234".to_string()), Some("5678".to_string())).unwrap();
+        assert!(!verify_pin("1234").unwrap());
+        assert!(unlock("1234".to_string()).is_err());
+
+        unlock("5678".to_string()).unwrap();
+        assert!(is_unlocked().unwrap());
+        let expiry = Duration::from_secs(DEFAULT_UNLOCK_MINUTES as u64 * 60 + 1);
+        assert!(!is_unlocked_at(Instant::now() + expiry).unwrap());
+        lock().unwrap();
+        assert!(!is_unlocked().unwrap());
+
+        set_pin(Some("5678".to_string()), None).unwrap();
+        assert!(!has_pin().unwrap());
+    }
+
+    #[test]
+    fn test_ensure_can_play() {
+        let _lock = test_db::lock();
+        let source_id = test_db::insert_source("parental play");
+        let channel = test_db::insert_channel(source_id, "Locked");
+        let other = test_db::insert_channel(source_id, "Other");
+        sql::lock_channel(channel.id.unwrap(), true).unwrap();
+        ensure_can_play(&channel).unwrap();
+
+        set_pin(None, Some("1234".to_string())).unwrap();
+        assert!(ensure_can_play(&channel).is_err());
+        ensure_can_play(&other).unwrap();
+        sql::lock_source(source_id, true).unwrap();
+        assert!(ensure_can_play(&other).is_err());
+        unlock("1234".to_string()).unwrap();
+        ensure_can_play(&channel).unwrap();
+        ensure_can_play(&other).unwrap();
+
+        set_pin(Some("1234".to_string()), None).unwrap();
+        sql::delete_source(source_id).unwrap();
+    }
+
+    #[test]
+    fn test_lockout() {
+        let _lock = test_db::lock();
+        set_pin(None, Some("1234".to_string())).unwrap();
+        let now = Instant::now();
+        for _ in 0..MAX_PIN_ATTEMPTS {
+            let error = check_pin_at("0000", now).unwrap_err();
+            assert_eq!(error.to_string(), "Invalid PIN");
+        }
+        let error = check_pin_at("1234", now).unwrap_err();
+        assert!(error.to_string().starts_with("Too many invalid PINs"));
+        assert!(unlock("1234".to_string()).is_err());
+        assert!(set_pin(Some("1234".to_string()), None).is_err());
+
+        check_pin_at("1234", now + PIN_LOCKOUT).unwrap();
+        set_pin(Some("1234".to_string()), None).unwrap();
+    }
+}
@@ -0,0 +1,126 @@
+use std::{fmt, sync::LazyLock};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::playback_error_type;
+
+static HTTP_STATUS_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)(?:http error|server returned|status(?: code)?:?)\s*(\d{3})"#).unwrap()
+});
+static TIMEOUT_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)timed out|timeout|etimedout"#).unwrap());
+static CODEC_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)codec|decoder|no video or audio streams|unsupported"#).unwrap()
+});
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+pub struct PlaybackError {
+    pub kind: u8,
+    pub message: String,
+    pub output: String,
+}
+
+impl fmt::Display for PlaybackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if !self.output.is_empty() {
+            write!(f, "\n{}", self.output)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for PlaybackError {}
+
+impl PlaybackError {
+    /// Whether trying the same stream again later may work
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self.kind,
+            playback_error_type::CONNECTION_LIMIT | playback_error_type::TIMEOUT
+        )
+    }
+}
+
+pub fn classify(output: &str) -> PlaybackError {
+    let kind = get_kind(output);
+    PlaybackError {
+        kind,
+        message: get_message(kind).to_string(),
+        output: output.trim().to_string(),
+    }
+}
+
+fn get_kind(output: &str) -> u8 {
+    let status = HTTP_STATUS_REGEX
+        .captures(output)
+        .and_then(|c| c.get(1))
+        .map(|m| m.as_str());
+    let lower = output.to_lowercase();
+    match status {
+        Some("403") => return playback_error_type::FORBIDDEN,
+        Some("404") => return playback_error_type::NOT_FOUND,
+        Some("429" | "509") => return playback_error_type::CONNECTION_LIMIT,
+        _ => {}
+    }
+    if lower.contains("bandwidth limit exceeded") || lower.contains("max connections") {
+        playback_error_type::CONNECTION_LIMIT
+    } else if lower.contains("forbidden") {
+        playback_error_type::FORBIDDEN
+    } else if lower.contains("not found") {
+        playback_error_type::NOT_FOUND
+    } else if TIMEOUT_REGEX.is_match(output) {
+        playback_error_type::TIMEOUT
+    } else if CODEC_REGEX.is_match(output) {
+        playback_error_type::CODEC
+    } else {
+        playback_error_type::UNKNOWN
+    }
+}
+
+fn get_message(kind: u8) -> &'static str {
+    match kind {
+        playback_error_type::FORBIDDEN => "Access denied by the provider, check your subscription",
+        playback_error_type::NOT_FOUND => "The stream is no longer available on the provider",
+        playback_error_type::CONNECTION_LIMIT => "Provider connection limit reached",
+        playback_error_type::TIMEOUT => "The provider took too long to answer",
+        playback_error_type::CODEC => "The stream uses a format the player can't decode",
+        _ => "The player encountered an unknown error",
+    }
+}
+
+#[cfg(test)]
+mod test_playback_error {
+    use super::*;
+
+    #[test]
+    fn test_classify_http_status() {
+        let error = classify(
+            "[ffmpeg] http: HTTP error 509 Bandwidth Limit Exceeded\nFailed to open http://example.com/live/404.ts",
+        );
+        assert_eq!(error.kind, playback_error_type::CONNECTION_LIMIT);
+        assert_eq!(error.message, "Provider connection limit reached");
+        assert!(error.is_transient());
+        let error = classify("[ffmpeg] https: HTTP error 403 Forbidden");
+        assert_eq!(error.kind, playback_error_type::FORBIDDEN);
+        assert!(!error.is_transient());
+        assert_eq!(
+            classify("Server returned 404 Not Found").kind,
+            playback_error_type::NOT_FOUND
+        );
+    }
+
+    #[test]
+    fn test_classify_other_errors() {
+        let error = classify("[ffmpeg] tcp: Connection timed out");
+        assert_eq!(error.kind, playback_error_type::TIMEOUT);
+        assert!(error.is_transient());
+        let error = classify("Could not open codec hevc");
+        assert_eq!(error.kind, playback_error_type::CODEC);
+        assert!(!error.is_transient());
+        let error = classify("");
+        assert_eq!(error.kind, playback_error_type::UNKNOWN);
+        assert!(!error.is_transient());
+    }
+}
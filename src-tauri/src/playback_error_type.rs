@@ -0,0 +1,6 @@
+pub const UNKNOWN: u8 = 0;
+pub const FORBIDDEN: u8 = 1;
+pub const NOT_FOUND: u8 = 2;
+pub const CONNECTION_LIMIT: u8 = 3;
+pub const TIMEOUT: u8 = 4;
+pub const CODEC: u8 = 5;
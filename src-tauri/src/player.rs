@@ -0,0 +1,234 @@
+use std::{env::consts::OS, path::Path, sync::LazyLock};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    log, player_type,
+    types::{HttpHeader, Settings, Source},
+    utils::{get_bin, to_ffmpeg_headers},
+};
+
+const VLC_BIN_NAME: &str = "vlc";
+const VLC_MACOS_PATH: &str = "/Applications/VLC.app/Contents/MacOS/VLC";
+const FFPLAY_BIN_NAME: &str = "ffplay";
+const PLACEHOLDER_URL: &str = "{url}";
+const PLACEHOLDER_TITLE: &str = "{title}";
+const PLACEHOLDER_USER_AGENT: &str = "{user_agent}";
+const PLACEHOLDER_REFERRER: &str = "{referrer}";
+const PLACEHOLDER_RECORD_PATH: &str = "{record_path}";
+const PLACEHOLDER_VOLUME: &str = "{volume}";
+static VLC_PATH: LazyLock<String> = LazyLock::new(get_vlc_path);
+static FFPLAY_PATH: LazyLock<String> = LazyLock::new(|| get_bin(FFPLAY_BIN_NAME));
+
+#[derive(Clone, Debug, Default)]
+pub struct PlayerRequest {
+    pub url: String,
+    pub title: String,
+    pub user_agent: Option<String>,
+    pub headers: Vec<HttpHeader>,
+    pub proxy: Option<String>,
+    pub record_path: Option<String>,
+    pub volume: Option<u8>,
+}
+
+impl PlayerRequest {
+    fn get_referrer(&self) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|h| h.name.eq_ignore_ascii_case("Referer"))
+            .map(|h| h.value.as_str())
+    }
+
+    fn get_other_headers(&self) -> Vec<HttpHeader> {
+        self.headers
+            .iter()
+            .filter(|h| !h.name.eq_ignore_ascii_case("Referer"))
+            .cloned()
+            .collect()
+    }
+}
+
+fn get_vlc_path() -> String {
+    if OS == "macos" && Path::new(VLC_MACOS_PATH).exists() {
+        return VLC_MACOS_PATH.to_string();
+    }
+    get_bin(VLC_BIN_NAME)
+}
+
+pub fn get_player_type(source: Option<&Source>, settings: &Settings) -> u8 {
+    source
+        .and_then(|s| s.player)
+        .or(settings.player)
+        .unwrap_or(player_type::MPV)
+}
+
+/// Builds the program and arguments for players other than mpv, which has its own richer path
+pub fn get_command(
+    player: u8,
+    request: &PlayerRequest,
+    settings: &Settings,
+) -> Result<(String, Vec<String>)> {
+    match player {
+        player_type::VLC => Ok((VLC_PATH.clone(), get_vlc_args(request))),
+        player_type::FFPLAY => Ok((FFPLAY_PATH.clone(), get_ffplay_args(request)?)),
+        player_type::CUSTOM => get_custom_command(
+            settings
+                .custom_player_command
+                .as_deref()
+                .context("No custom player command set in Settings")?,
+            request,
+        ),
+        _ => bail!("Unsupported player: {player}"),
+    }
+}
+
+fn get_vlc_args(request: &PlayerRequest) -> Vec<String> {
+    let mut args = vec![
+        request.url.clone(),
+        format!("--meta-title={}", request.title),
+        "--play-and-exit".to_string(),
+        "--quiet".to_string(),
+    ];
+    if let Some(user_agent) = request.user_agent.as_ref() {
+        args.push(format!("--http-user-agent={user_agent}"));
+    }
+    if let Some(referrer) = request.get_referrer() {
+        args.push(format!("--http-referrer={referrer}"));
+    }
+    let other_headers = request.get_other_headers();
+    if !other_headers.is_empty() {
+        log::log(format!(
+            "VLC can't send custom headers, ignoring {} header(s)",
+            other_headers.len()
+        ));
+    }
+    if let Some(proxy) = request.proxy.as_ref() {
+        args.push(format!("--http-proxy={proxy}"));
+    }
+    if let Some(volume) = request.volume {
+        args.push(format!("--gain={}", volume as f32 / 100.0));
+    }
+    if let Some(path) = request.record_path.as_ref() {
+        args.push(format!(
+            "--sout=#duplicate{{dst=display,dst=std{{access=file,mux=ts,dst='{path}'}}}}"
+        ));
+    }
+    args
+}
+
+fn get_ffplay_args(request: &PlayerRequest) -> Result<Vec<String>> {
+    if request.record_path.is_some() {
+        bail!("ffplay can't record, pick another player to record");
+    }
+    let mut args = vec![
+        "-autoexit".to_string(),
+        "-loglevel".to_string(),
+        "error".to_string(),
+        "-window_title".to_string(),
+        request.title.clone(),
+    ];
+    if let Some(user_agent) = request.user_agent.as_ref() {
+        args.push("-user_agent".to_string());
+        args.push(user_agent.clone());
+    }
+    if let Some(headers) = to_ffmpeg_headers(&request.headers) {
+        args.push("-headers".to_string());
+        args.push(headers);
+    }
+    if let Some(proxy) = request.proxy.as_ref() {
+        args.push("-http_proxy".to_string());
+        args.push(proxy.clone());
+    }
+    if let Some(volume) = request.volume {
+        args.push("-volume".to_string());
+        args.push(volume.to_string());
+    }
+    args.push(request.url.clone());
+    Ok(args)
+}
+
+fn get_custom_command(template: &str, request: &PlayerRequest) -> Result<(String, Vec<String>)> {
+    if request.record_path.is_some() && !template.contains(PLACEHOLDER_RECORD_PATH) {
+        bail!("The custom player command has no {PLACEHOLDER_RECORD_PATH} placeholder to record");
+    }
+    #[cfg(not(target_os = "windows"))]
+    let parts = shell_words::split(template)?;
+    #[cfg(target_os = "windows")]
+    let parts = winsplit::split(template);
+    let volume = request.volume.map(|v| v.to_string());
+    let mut parts = parts.into_iter().map(|part| {
+        part.replace(PLACEHOLDER_URL, &request.url)
+            .replace(PLACEHOLDER_TITLE, &request.title)
+            .replace(
+                PLACEHOLDER_USER_AGENT,
+                request.user_agent.as_deref().unwrap_or_default(),
+            )
+            .replace(
+                PLACEHOLDER_REFERRER,
+                request.get_referrer().unwrap_or_default(),
+            )
+            .replace(
+                PLACEHOLDER_RECORD_PATH,
+                request.record_path.as_deref().unwrap_or_default(),
+            )
+            .replace(PLACEHOLDER_VOLUME, volume.as_deref().unwrap_or_default())
+    });
+    let program = parts.next().context("The custom player command is empty")?;
+    Ok((program, parts.collect()))
+}
+
+#[cfg(test)]
+mod test_player {
+    use super::*;
+
+    fn get_request() -> PlayerRequest {
+        PlayerRequest {
+            url: "http://example.com/live/1.ts?a=1&b=2".to_string(),
+            title: "My Channel".to_string(),
+            user_agent: Some("Agent/1.0".to_string()),
+            headers: vec![HttpHeader {
+                name: "Referer".to_string(),
+                value: "http://example.com".to_string(),
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_custom_command_placeholders() {
+        let (program, args) = get_custom_command(
+            "player --title \"{title}\" --ua={user_agent} --ref={referrer} {url}",
+            &get_request(),
+        )
+        .unwrap();
+        assert_eq!(program, "player");
+        assert_eq!(
+            args,
+            vec![
+                "--title",
+                "My Channel",
+                "--ua=Agent/1.0",
+                "--ref=http://example.com",
+                "http://example.com/live/1.ts?a=1&b=2",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_custom_command_record_requires_placeholder() {
+        let request = PlayerRequest {
+            record_path: Some("/tmp/out.ts".to_string()),
+            ..get_request()
+        };
+        assert!(get_custom_command("player {url}", &request).is_err());
+    }
+
+    #[test]
+    fn test_ffplay_rejects_recording() {
+        let request = PlayerRequest {
+            record_path: Some("/tmp/out.ts".to_string()),
+            ..get_request()
+        };
+        assert!(get_ffplay_args(&request).is_err());
+    }
+}
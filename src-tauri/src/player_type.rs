@@ -0,0 +1,4 @@
+pub const MPV: u8 = 0;
+pub const VLC: u8 = 1;
+pub const FFPLAY: u8 = 2;
+pub const CUSTOM: u8 = 3;
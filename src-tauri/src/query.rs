@@ -0,0 +1,394 @@
+use std::{iter::Peekable, str::Chars};
+
+use anyhow::{Result, bail};
+
+use crate::{media_type, relevance::FUZZY_THRESHOLD};
+
+pub const CHANNELS_FTS: &str = "channels_fts";
+pub const GROUPS_FTS: &str = "groups_fts";
+pub const SEASONS_FTS: &str = "seasons_fts";
+// FTS only matches tokens by prefix, shorter words are often inside others like "hd" in "FHD"
+const MIN_PREFIX_LEN: usize = 4;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SearchTable {
+    Channels,
+    Groups,
+    Seasons,
+}
+
+impl SearchTable {
+    fn fts(&self) -> &'static str {
+        match self {
+            SearchTable::Channels => CHANNELS_FTS,
+            SearchTable::Groups => GROUPS_FTS,
+            SearchTable::Seasons => SEASONS_FTS,
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct SearchQuery {
+    pub words: Vec<String>,
+    pub phrases: Vec<String>,
+    pub excluded: Vec<String>,
+    pub group: Option<String>,
+    pub source: Option<String>,
+    pub media_type: Option<u8>,
+    pub favorite: Option<bool>,
+    pub archive: Option<bool>,
+    match_query: Option<String>,
+    like_queries: Vec<String>,
+    short_query: bool,
+    fuzzy_query: Option<String>,
+    excluded_query: Option<String>,
+}
+
+pub fn parse(query: &str, use_keywords: bool, fuzzy: bool) -> Result<SearchQuery> {
+    let mut search = SearchQuery::default();
+    let mut chars = query.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            break;
+        }
+        let negated = chars.next_if_eq(&'-').is_some();
+        let quoted = chars.peek() == Some(&'"');
+        let term = read_term(&mut chars)?;
+        if term.is_empty() {
+            continue;
+        }
+        if !quoted && let Some((key, value)) = term.split_once(':') {
+            let key = key.to_lowercase();
+            if is_filter_key(&key) {
+                if negated {
+                    bail!("Filter \"{key}:\" can't be negated");
+                }
+                set_filter(&mut search, &key, value)?;
+                continue;
+            }
+        }
+        if negated {
+            search.excluded.push(term);
+        } else if quoted {
+            search.phrases.push(term);
+        } else {
+            search.words.push(term);
+        }
+    }
+    search.build_fts_queries(use_keywords, fuzzy);
+    Ok(search)
+}
+
+fn read_term(chars: &mut Peekable<Chars>) -> Result<String> {
+    let mut term = String::new();
+    let mut in_quote = false;
+    while let Some(c) = chars.next_if(|c| in_quote || !c.is_whitespace()) {
+        match c {
+            '"' => in_quote = !in_quote,
+            _ => term.push(c),
+        }
+    }
+    if in_quote {
+        bail!("Unterminated quote in search query");
+    }
+    Ok(term.trim().to_string())
+}
+
+fn is_filter_key(key: &str) -> bool {
+    matches!(key, "group" | "source" | "type" | "fav" | "archive")
+}
+
+fn set_filter(search: &mut SearchQuery, key: &str, value: &str) -> Result<()> {
+    let value = value.trim();
+    if value.is_empty() {
+        bail!("Missing value for \"{key}:\"");
+    }
+    match key {
+        "group" => search.group = Some(format!("%{}%", escape_like(value))),
+        "source" => search.source = Some(format!("%{}%", escape_like(value))),
+        "type" => search.media_type = Some(parse_media_type(value)?),
+        "fav" => search.favorite = Some(parse_bool(key, value)?),
+        _ => search.archive = Some(parse_bool(key, value)?),
+    }
+    Ok(())
+}
+
+/// Pairs with ESCAPE '\' so wildcards in names match literally
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+fn parse_media_type(value: &str) -> Result<u8> {
+    match value.to_lowercase().as_str() {
+        "live" | "livestream" | "channel" => Ok(media_type::LIVESTREAM),
+        "movie" | "movies" | "vod" => Ok(media_type::MOVIE),
+        "series" | "serie" | "show" => Ok(media_type::SERIE),
+        _ => bail!("Invalid value \"{value}\" for \"type:\", expected live, movie or series"),
+    }
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool> {
+    match value.to_lowercase().as_str() {
+        "true" | "yes" | "1" => Ok(true),
+        "false" | "no" | "0" => Ok(false),
+        _ => bail!("Invalid value \"{value}\" for \"{key}:\", expected true or false"),
+    }
+}
+
+fn get_tokens(value: &str) -> Vec<&str> {
+    value
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .collect()
+}
+
+impl SearchQuery {
+    fn build_fts_queries(&mut self, use_keywords: bool, fuzzy: bool) {
+        let words: Vec<&str> = self.words.iter().flat_map(|w| get_tokens(w)).collect();
+        let mut matches: Vec<String> = match use_keywords {
+            true => words.iter().map(|token| format!("\"{token}\"*")).collect(),
+            false if !words.is_empty() => vec![format!("\"{}\"*", words.join(" "))],
+            false => Vec::new(),
+        };
+        matches.extend(
+            self.phrases
+                .iter()
+                .map(|p| get_tokens(p).join(" "))
+                .filter(|p| !p.is_empty())
+                .map(|p| format!("\"{p}\"")),
+        );
+        self.match_query = (!matches.is_empty()).then(|| matches.join(" "));
+        let phrases = self.phrases.iter().map(|p| get_tokens(p).join("%"));
+        self.like_queries = match use_keywords {
+            true => words.iter().map(|token| token.to_string()).collect(),
+            false if !words.is_empty() => vec![words.join("%")],
+            false => Vec::new(),
+        }
+        .into_iter()
+        .chain(phrases)
+        .filter(|p| !p.is_empty())
+        .map(|p| format!("%{p}%"))
+        .collect();
+        self.short_query = words.iter().any(|w| w.chars().count() < MIN_PREFIX_LEN);
+        self.fuzzy_query = (fuzzy && !words.is_empty()).then(|| words.join(" "));
+        let excluded: Vec<String> = self
+            .excluded
+            .iter()
+            .map(|e| get_tokens(e).join(" "))
+            .filter(|e| !e.is_empty())
+            .map(|e| format!("\"{e}\""))
+            .collect();
+        self.excluded_query = (!excluded.is_empty()).then(|| excluded.join(" OR "));
+    }
+
+    pub fn get_text(&self) -> String {
+        self.words
+            .iter()
+            .chain(self.phrases.iter())
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn to_sql(&self, table: SearchTable) -> String {
+        let fts = table.fts();
+        let mut conditions: Vec<String> = Vec::new();
+        if self.match_query.is_some() {
+            let mut condition = format!("id IN (SELECT rowid FROM {fts} WHERE {fts} MATCH ?)");
+            // Substring and fuzzy matching scan every row, only worth it when FTS can't find anything
+            let mut unmatched = Vec::new();
+            if !self.like_queries.is_empty() {
+                let like = format!(
+                    "({})",
+                    vec!["name LIKE ?"; self.like_queries.len()].join(" AND ")
+                );
+                match self.short_query {
+                    true => condition = format!("{condition} OR {like}"),
+                    false => unmatched.push(like),
+                }
+            }
+            if self.fuzzy_query.is_some() {
+                unmatched.push(format!("similarity(name, ?) >= {FUZZY_THRESHOLD}"));
+            }
+            if !unmatched.is_empty() {
+                condition = format!(
+                    "{condition} OR (NOT EXISTS (SELECT 1 FROM {fts} WHERE {fts} MATCH ?) AND ({}))",
+                    unmatched.join(" OR ")
+                );
+            }
+            conditions.push(format!("({condition})"));
+        }
+        if self.excluded_query.is_some() {
+            conditions.push(format!(
+                "id NOT IN (SELECT rowid FROM {fts} WHERE {fts} MATCH ?)"
+            ));
+        }
+        if self.source.is_some() {
+            conditions.push(
+                "source_id IN (SELECT id FROM sources WHERE name LIKE ? ESCAPE '\\')".to_string(),
+            );
+        }
+        if self.group.is_some() && table != SearchTable::Seasons {
+            conditions.push(match table {
+                SearchTable::Channels => {
+                    "group_id IN (SELECT id FROM groups WHERE name LIKE ? ESCAPE '\\')".to_string()
+                }
+                _ => "name LIKE ? ESCAPE '\\'".to_string(),
+            });
+        }
+        if table == SearchTable::Channels {
+            if self.media_type.is_some() {
+                conditions.push("media_type = ?".to_string());
+            }
+            if self.favorite.is_some() {
+                conditions.push("favorite = ?".to_string());
+            }
+            if self.archive.is_some() {
+                conditions.push("COALESCE(tv_archive, 0) = ?".to_string());
+            }
+        }
+        if conditions.is_empty() {
+            return "1 = 1".to_string();
+        }
+        conditions.join(" AND ")
+    }
+
+    pub fn params(&self, table: SearchTable) -> Vec<&dyn rusqlite::ToSql> {
+        let mut params: Vec<&dyn rusqlite::ToSql> = Vec::new();
+        if let Some(match_query) = self.match_query.as_ref() {
+            params.push(match_query);
+            let like_queries = self.like_queries.iter().map(|l| l as &dyn rusqlite::ToSql);
+            if self.short_query {
+                params.extend(like_queries);
+                if let Some(fuzzy_query) = self.fuzzy_query.as_ref() {
+                    params.push(match_query);
+                    params.push(fuzzy_query);
+                }
+            } else if !self.like_queries.is_empty() {
+                params.push(match_query);
+                params.extend(like_queries);
+                if let Some(fuzzy_query) = self.fuzzy_query.as_ref() {
+                    params.push(fuzzy_query);
+                }
+            }
+        }
+        if let Some(excluded_query) = self.excluded_query.as_ref() {
+            params.push(excluded_query);
+        }
+        if let Some(source) = self.source.as_ref() {
+            params.push(source);
+        }
+        if let Some(group) = self.group.as_ref()
+            && table != SearchTable::Seasons
+        {
+            params.push(group);
+        }
+        if table == SearchTable::Channels {
+            if let Some(media_type) = self.media_type.as_ref() {
+                params.push(media_type);
+            }
+            if let Some(favorite) = self.favorite.as_ref() {
+                params.push(favorite);
+            }
+            if let Some(archive) = self.archive.as_ref() {
+                params.push(archive);
+            }
+        }
+        params
+    }
+}
+
+#[cfg(test)]
+mod test_query {
+    use super::{SearchTable, parse};
+    use crate::media_type;
+
+    #[test]
+    fn test_parse() {
+        let search = parse(
+            r#"bbc "news at ten" -kids group:sports source:"My IPTV" type:movie fav:true archive:no UK:"#,
+            true,
+            false,
+        )
+        .unwrap();
+        assert_eq!(search.words, vec!["bbc", "UK:"]);
+        assert_eq!(search.phrases, vec!["news at ten"]);
+        assert_eq!(search.excluded, vec!["kids"]);
+        assert_eq!(search.group.as_deref(), Some("%sports%"));
+        assert_eq!(search.source.as_deref(), Some("%My IPTV%"));
+        assert_eq!(search.media_type, Some(media_type::MOVIE));
+        assert_eq!(search.favorite, Some(true));
+        assert_eq!(search.archive, Some(false));
+        assert_eq!(
+            search.match_query.as_deref(),
+            Some(r#""bbc"* "UK"* "news at ten""#)
+        );
+        assert_eq!(search.excluded_query.as_deref(), Some(r#""kids""#));
+        assert_eq!(search.params(SearchTable::Channels).len(), 10);
+        assert_eq!(search.params(SearchTable::Seasons).len(), 6);
+    }
+
+    #[test]
+    fn test_fts_queries() {
+        let search = parse(r#"abc*"def" news* "bbc* one""#, true, false).unwrap();
+        assert_eq!(search.words, vec!["abc*def", "news*"]);
+        assert_eq!(
+            search.match_query.as_deref(),
+            Some(r#""abc"* "def"* "news"* "bbc one""#)
+        );
+        assert_eq!(
+            search.like_queries,
+            vec!["%abc%", "%def%", "%news%", "%bbc%one%"]
+        );
+        assert!(search.short_query);
+        assert_eq!(
+            search.to_sql(SearchTable::Groups),
+            "(id IN (SELECT rowid FROM groups_fts WHERE groups_fts MATCH ?) OR (name LIKE ? AND name LIKE ? AND name LIKE ? AND name LIKE ?))"
+        );
+
+        let search = parse("foxes newsroom", false, false).unwrap();
+        assert_eq!(search.match_query.as_deref(), Some(r#""foxes newsroom"*"#));
+        assert_eq!(search.like_queries, vec!["%foxes%newsroom%"]);
+        assert!(!search.short_query);
+        assert_eq!(
+            search.to_sql(SearchTable::Groups),
+            "(id IN (SELECT rowid FROM groups_fts WHERE groups_fts MATCH ?) OR (NOT EXISTS (SELECT 1 FROM groups_fts WHERE groups_fts MATCH ?) AND ((name LIKE ?))))"
+        );
+        assert_eq!(search.params(SearchTable::Groups).len(), 3);
+
+        let search = parse("bbc", true, true).unwrap();
+        assert_eq!(
+            search.to_sql(SearchTable::Groups),
+            "(id IN (SELECT rowid FROM groups_fts WHERE groups_fts MATCH ?) OR (name LIKE ?) OR (NOT EXISTS (SELECT 1 FROM groups_fts WHERE groups_fts MATCH ?) AND (similarity(name, ?) >= 0.5)))"
+        );
+        assert_eq!(search.params(SearchTable::Groups).len(), 4);
+    }
+
+    #[test]
+    fn test_escape_filters() {
+        let search = parse(r"group:100%_sport source:c:\tv", true, false).unwrap();
+        assert_eq!(search.group.as_deref(), Some(r"%100\%\_sport%"));
+        assert_eq!(search.source.as_deref(), Some(r"%c:\\tv%"));
+        assert_eq!(
+            search.to_sql(SearchTable::Groups),
+            r"source_id IN (SELECT id FROM sources WHERE name LIKE ? ESCAPE '\') AND name LIKE ? ESCAPE '\'"
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse(r#"source:"My IPTV"#, true, false).is_err());
+        assert!(parse("type:radio", true, false).is_err());
+        assert!(parse("fav:maybe", true, false).is_err());
+        assert!(parse("group:", true, false).is_err());
+        assert!(parse("-group:sports", true, false).is_err());
+        assert_eq!(
+            parse("", true, false).unwrap().to_sql(SearchTable::Groups),
+            "1 = 1"
+        );
+    }
+}
@@ -0,0 +1,92 @@
+use std::collections::HashSet;
+
+use rusqlite::{Connection, functions::FunctionFlags};
+
+use crate::name_rules::fold_for_search;
+
+pub const FUZZY_THRESHOLD: f64 = 0.5;
+const EXACT: u8 = 0;
+const PREFIX: u8 = 1;
+const WORD: u8 = 2;
+const CONTAINS: u8 = 3;
+const OTHER: u8 = 4;
+
+pub fn register_functions(conn: &Connection) -> rusqlite::Result<()> {
+    let flags = FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC;
+    conn.create_scalar_function("relevance", 2, flags, |ctx| {
+        let name = ctx.get::<Option<String>>(0)?.unwrap_or_default();
+        let query = ctx.get::<Option<String>>(1)?.unwrap_or_default();
+        Ok(get_relevance(&name, &query))
+    })?;
+    conn.create_scalar_function("similarity", 2, flags, |ctx| {
+        let name = ctx.get::<Option<String>>(0)?.unwrap_or_default();
+        let query = ctx.get::<Option<String>>(1)?.unwrap_or_default();
+        Ok(get_similarity(&name, &query))
+    })
+}
+
+pub fn get_relevance(name: &str, query: &str) -> u8 {
+    let query = fold_for_search(query);
+    if query.is_empty() {
+        return EXACT;
+    }
+    let name = fold_for_search(name);
+    if name == query {
+        EXACT
+    } else if name.starts_with(&query) {
+        PREFIX
+    } else if format!(" {name} ").contains(&format!(" {query} ")) {
+        WORD
+    } else if name.contains(&query) {
+        CONTAINS
+    } else {
+        OTHER
+    }
+}
+
+/// Share of the query's trigrams found in the name, so long names are not penalized.
+pub fn get_similarity(name: &str, query: &str) -> f64 {
+    let query = get_trigrams(&fold_for_search(query));
+    if query.is_empty() {
+        return 0.0;
+    }
+    let name = get_trigrams(&fold_for_search(name));
+    query.intersection(&name).count() as f64 / query.len() as f64
+}
+
+fn get_trigrams(value: &str) -> HashSet<[char; 3]> {
+    value
+        .split(' ')
+        .filter(|word| !word.is_empty())
+        .flat_map(|word| {
+            let chars: Vec<char> = format!("  {word} ").chars().collect();
+            chars
+                .windows(3)
+                .map(|w| [w[0], w[1], w[2]])
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test_relevance {
+    use super::{FUZZY_THRESHOLD, get_relevance, get_similarity};
+
+    #[test]
+    fn test_get_relevance() {
+        assert_eq!(get_relevance("BBC One", "bbc one"), 0);
+        assert_eq!(get_relevance("BBC One Scotland HD+1", "BBC One"), 1);
+        assert_eq!(get_relevance("UK: BBC One", "bbc one"), 2);
+        assert_eq!(get_relevance("CBBC", "bbc"), 3);
+        assert_eq!(get_relevance("Télé Matin", "tele"), 1);
+        assert_eq!(get_relevance("ᴮᴮᶜ One", "bbc"), 1);
+        assert_eq!(get_relevance("ITV", "bbc"), 4);
+    }
+
+    #[test]
+    fn test_get_similarity() {
+        assert!(get_similarity("Discovery Channel", "discovry") >= FUZZY_THRESHOLD);
+        assert!(get_similarity("Eurosport 1", "eurosprot") >= FUZZY_THRESHOLD);
+        assert!(get_similarity("Cartoon Network", "discovry") < FUZZY_THRESHOLD);
+    }
+}
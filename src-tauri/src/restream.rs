@@ -1,4 +1,5 @@
 use std::{
+    collections::HashMap,
     path::{Path, PathBuf},
     process::{Child, Command, Stdio},
     time::Duration,
@@ -7,7 +8,8 @@ use std::{
 #[cfg(target_os = "windows")]
 use std::os::windows::process::CommandExt;
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
 use tauri::{AppHandle, Emitter, State};
 use tokio::{
     fs,
@@ -18,49 +20,84 @@ use tokio::{
 };
 
 use crate::{
-    mpv,
+    drm,
+    log::log,
+    mpv, parental,
     settings::get_settings,
     sql,
-    types::{AppState, Channel, CustomChannel, NetworkInfo},
-    utils::{get_bin, serialize_to_file},
+    types::{AppState, Channel, CustomChannel, NetworkInfo, Source},
+    utils::{get_bin, get_http_headers, get_http_proxy, serialize_to_file, to_ffmpeg_headers},
+    xtream,
 };
 
 const WAN_IP_API: &str = "https://api.ipify.org";
 const FFMPEG_BIN_NAME: &str = "ffmpeg";
+const FFPROBE_BIN_NAME: &str = "ffprobe";
+const PROBE_TIMEOUT: Duration = Duration::from_secs(20);
+const TEXT_SUBTITLE_CODECS: [&str; 5] = ["subrip", "ass", "ssa", "mov_text", "webvtt"];
 #[cfg(target_os = "windows")]
 const CREATE_NO_WINDOW: u32 = 0x08000000;
 
-fn start_ffmpeg_listening(channel: Channel, restream_dir: PathBuf) -> Result<Child> {
-    let headers = sql::get_channel_headers_by_id(channel.id.context("no channel id")?)?;
+#[derive(Deserialize)]
+struct ProbeOutput {
+    #[serde(default)]
+    streams: Vec<ProbedStream>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+struct ProbedStream {
+    index: usize,
+    #[serde(default)]
+    codec_type: String,
+    #[serde(default)]
+    codec_name: String,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+}
+
+impl ProbedStream {
+    fn get_language(&self) -> Option<String> {
+        self.tags.get("language").map(|l| l.to_lowercase())
+    }
+}
+
+async fn start_ffmpeg_listening(channel: Channel, restream_dir: PathBuf) -> Result<Child> {
+    let channel_id = channel.id.context("no channel id")?;
+    let source = channel
+        .source_id
+        .and_then(|id| sql::get_source_from_id(id).ok());
+    let overrides = mpv::get_playback_overrides(channel_id, &source, &get_settings()?)?;
+    let url = xtream::resolve_url(channel.url.context("no channel url")?, source.as_ref())?;
+    let input_args = get_input_args(channel_id, &source)?;
+    let audio_languages = split_languages(overrides.audio_languages.as_deref());
+    let subtitle_languages = split_languages(overrides.subtitle_languages.as_deref());
+    let streams = if audio_languages.is_empty() && subtitle_languages.is_empty() {
+        Vec::new()
+    } else {
+        probe_streams(&input_args, &url).await.unwrap_or_else(|e| {
+            log(format!(
+                "Could not probe tracks, using default mapping: {:?}",
+                e
+            ));
+            Vec::new()
+        })
+    };
+    let (map_args, has_subtitles) = get_map_args(&streams, &audio_languages, &subtitle_languages);
     let playlist_dir = get_playlist_dir(restream_dir);
     let mut command = Command::new(get_bin(FFMPEG_BIN_NAME));
-    if let Some(headers) = headers {
-        if let Some(referrer) = headers.referrer {
-            command.arg("-headers");
-            command.arg(format!("Referer: {referrer}"));
-        }
-        if let Some(user_agent) = headers.user_agent {
-            command.arg("-headers");
-            command.arg(format!("User-Agent: {user_agent}"));
-        }
-        if let Some(origin) = headers.http_origin {
-            command.arg("-headers");
-            command.arg(format!("Origin: {origin}"));
-        }
-        if let Some(ignore_ssl) = headers.ignore_ssl {
-            if ignore_ssl {
-                command.arg("-tls_verify");
-                command.arg("0");
-            }
-        }
-    }
     #[cfg(target_os = "windows")]
     command.creation_flags(CREATE_NO_WINDOW);
-    let child = command
+    command
+        .args(input_args)
         .arg("-i")
-        .arg(channel.url.context("no channel url")?)
+        .arg(url)
+        .args(map_args)
         .arg("-c")
-        .arg("copy")
+        .arg("copy");
+    if has_subtitles {
+        command.arg("-c:s").arg("webvtt");
+    }
+    let child = command
         .arg("-f")
         .arg("hls")
         .arg("-hls_time")
@@ -84,6 +121,110 @@ fn start_ffmpeg_listening(channel: Channel, restream_dir: PathBuf) -> Result<Chi
     Ok(child)
 }
 
+fn get_input_args(channel_id: i64, source: &Option<Source>) -> Result<Vec<String>> {
+    let headers = sql::get_channel_headers_by_id(channel_id)?;
+    let http_headers = get_http_headers(headers.as_ref(), source.as_ref().and_then(|s| s.id));
+    let mut args = Vec::new();
+    if let Some(http_headers) = to_ffmpeg_headers(&http_headers) {
+        args.push("-headers".to_string());
+        args.push(http_headers);
+    }
+    if let Some(headers) = headers {
+        if let Some(user_agent) = headers.user_agent {
+            args.push("-user_agent".to_string());
+            args.push(user_agent);
+        }
+        if headers.ignore_ssl == Some(true) {
+            args.push("-tls_verify".to_string());
+            args.push("0".to_string());
+        }
+    }
+    if let Some(proxy) = source.as_ref().map(get_http_proxy).transpose()?.flatten() {
+        args.push("-http_proxy".to_string());
+        args.push(proxy);
+    }
+    if let Some(key) = sql::get_channel_drm(channel_id)?
+        .as_ref()
+        .and_then(drm::get_clearkey)
+    {
+        args.push("-decryption_key".to_string());
+        args.push(key);
+    }
+    Ok(args)
+}
+
+fn split_languages(languages: Option<&str>) -> Vec<String> {
+    languages
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+async fn probe_streams(input_args: &[String], url: &str) -> Result<Vec<ProbedStream>> {
+    let mut command = tokio::process::Command::new(get_bin(FFPROBE_BIN_NAME));
+    #[cfg(target_os = "windows")]
+    command.creation_flags(CREATE_NO_WINDOW);
+    command
+        .arg("-v")
+        .arg("error")
+        .args(input_args)
+        .arg("-show_entries")
+        .arg("stream=index,codec_type,codec_name:stream_tags=language")
+        .arg("-of")
+        .arg("json")
+        .arg(url)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true);
+    let output = tokio::time::timeout(PROBE_TIMEOUT, command.output()).await??;
+    if !output.status.success() {
+        bail!("ffprobe could not read the stream");
+    }
+    Ok(serde_json::from_slice::<ProbeOutput>(&output.stdout)?.streams)
+}
+
+fn find_tracks<'a>(
+    streams: &'a [ProbedStream],
+    codec_type: &str,
+    languages: &'a [String],
+) -> impl Iterator<Item = &'a ProbedStream> {
+    languages.iter().flat_map(move |language| {
+        streams.iter().filter(move |s| {
+            s.codec_type == codec_type && s.get_language().as_deref() == Some(language.as_str())
+        })
+    })
+}
+
+/// Maps the tracks in the preferred languages, in order, so clients pick the first by default.
+/// Every audio track is only kept when none is in a preferred language. Only text subtitles are
+/// mapped since they have to be converted to WebVTT for HLS.
+fn get_map_args(
+    streams: &[ProbedStream],
+    audio_languages: &[String],
+    subtitle_languages: &[String],
+) -> (Vec<String>, bool) {
+    if audio_languages.is_empty() && subtitle_languages.is_empty() {
+        return (Vec::new(), false);
+    }
+    let mut args = vec!["-map".to_string(), "0:v?".to_string()];
+    let audio: Vec<&ProbedStream> = find_tracks(streams, "audio", audio_languages).collect();
+    if audio.is_empty() {
+        args.push("-map".to_string());
+        args.push("0:a?".to_string());
+    }
+    let subtitles: Vec<&ProbedStream> = find_tracks(streams, "subtitle", subtitle_languages)
+        .filter(|s| TEXT_SUBTITLE_CODECS.contains(&s.codec_name.as_str()))
+        .collect();
+    for stream in audio.iter().chain(subtitles.iter()) {
+        args.push("-map".to_string());
+        args.push(format!("0:{}", stream.index));
+    }
+    (args, !subtitles.is_empty())
+}
+
 async fn start_web_server(
     restream_dir: PathBuf,
     port: u16,
@@ -104,11 +245,12 @@ pub async fn start_restream(
     app: AppHandle,
     channel: Channel,
 ) -> Result<()> {
+    parental::ensure_can_play(&channel)?;
     let stop = state.lock().await.restream_stop_signal.clone();
     stop.store(false, std::sync::atomic::Ordering::Relaxed);
     let restream_dir = get_restream_folder()?;
     delete_old_segments(&restream_dir).await?;
-    let mut ffmpeg_child = start_ffmpeg_listening(channel, restream_dir.clone())?;
+    let mut ffmpeg_child = start_ffmpeg_listening(channel, restream_dir.clone()).await?;
     let (web_server_tx, web_server_handle) = start_web_server(restream_dir, port).await?;
     let _ = app.emit("restream_started", true);
     while !stop.load(std::sync::atomic::Ordering::Relaxed)
@@ -158,7 +300,11 @@ async fn delete_old_segments(dir: &Path) -> Result<()> {
     Ok(())
 }
 
-pub async fn watch_self(port: u16, state: State<'_, Mutex<AppState>>) -> Result<()> {
+pub async fn watch_self(
+    port: u16,
+    state: State<'_, Mutex<AppState>>,
+    app: AppHandle,
+) -> Result<()> {
     let channel = Channel {
         url: Some(format!("http://127.0.0.1:{port}/stream.m3u8").to_string()),
         name: "Local livestream".to_string(),
@@ -175,8 +321,11 @@ pub async fn watch_self(port: u16, state: State<'_, Mutex<AppState>>) -> Result<
         season_id: None,
         episode_num: None,
         hidden: Some(false),
+        original_name: None,
+        tvg_id: None,
+        progress: None,
     };
-    mpv::play(channel, false, None, state).await
+    mpv::play(channel, mpv::PlayOptions::default(), state, app).await
 }
 
 pub fn share_restream(address: String, channel: Channel, path: String) -> Result<()> {
@@ -198,6 +347,9 @@ pub fn share_restream(address: String, channel: Channel, path: String) -> Result
             season_id: None,
             episode_num: None,
             hidden: Some(false),
+            original_name: None,
+            tvg_id: None,
+            progress: None,
         },
     };
     serialize_to_file(channel, path)
@@ -226,3 +378,61 @@ async fn get_wan_ip(port: u16) -> Result<String> {
         reqwest::get(WAN_IP_API).await?.text().await?
     ))
 }
+
+#[cfg(test)]
+mod test_restream {
+    use super::*;
+
+    fn stream(index: usize, codec_type: &str, codec_name: &str, language: &str) -> ProbedStream {
+        ProbedStream {
+            index,
+            codec_type: codec_type.to_string(),
+            codec_name: codec_name.to_string(),
+            tags: HashMap::from([("language".to_string(), language.to_string())]),
+        }
+    }
+
+    fn get_streams() -> Vec<ProbedStream> {
+        vec![
+            stream(0, "video", "h264", "und"),
+            stream(1, "audio", "aac", "eng"),
+            stream(2, "audio", "aac", "FRE"),
+            stream(3, "subtitle", "subrip", "fre"),
+            stream(4, "subtitle", "dvb_subtitle", "eng"),
+        ]
+    }
+
+    fn languages(languages: &str) -> Vec<String> {
+        split_languages(Some(languages))
+    }
+
+    #[test]
+    fn test_map_args_without_languages() {
+        assert_eq!(get_map_args(&get_streams(), &[], &[]), (Vec::new(), false));
+    }
+
+    #[test]
+    fn test_map_args_preferred_audio() {
+        let (args, has_subtitles) = get_map_args(&get_streams(), &languages("fre, eng"), &[]);
+        assert_eq!(args, ["-map", "0:v?", "-map", "0:2", "-map", "0:1"]);
+        assert!(!has_subtitles);
+    }
+
+    #[test]
+    fn test_map_args_audio_fallback() {
+        let (args, _) = get_map_args(&get_streams(), &languages("spa"), &[]);
+        assert_eq!(args, ["-map", "0:v?", "-map", "0:a?"]);
+    }
+
+    #[test]
+    fn test_map_args_subtitles() {
+        let (args, has_subtitles) =
+            get_map_args(&get_streams(), &languages("eng"), &languages("eng,fre"));
+        // The bitmap english track can't be converted to WebVTT
+        assert_eq!(args, ["-map", "0:v?", "-map", "0:1", "-map", "0:3"]);
+        assert!(has_subtitles);
+        let (args, has_subtitles) = get_map_args(&get_streams(), &[], &languages("eng"));
+        assert_eq!(args, ["-map", "0:v?", "-map", "0:a?"]);
+        assert!(!has_subtitles);
+    }
+}
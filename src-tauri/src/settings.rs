@@ -18,6 +18,19 @@ pub const DEFAULT_SORT: &str = "defaultSort";
 pub const ENABLE_HWDEC: &str = "enableHWDEC";
 pub const ALWAYS_ASK_SAVE: &str = "alwaysAskSave";
 pub const ENABLE_GPU: &str = "enableGPU";
+pub const MERGE_DUPLICATES: &str = "mergeDuplicates";
+pub const DEFAULT_PROXY: &str = "defaultProxy";
+pub const PAGE_SIZE: &str = "pageSize";
+pub const PARENTAL_AUTO_LOCK: &str = "parentalAutoLock";
+pub const PARENTAL_KEYWORDS: &str = "parentalKeywords";
+pub const PARENTAL_UNLOCK_MINUTES: &str = "parentalUnlockMinutes";
+pub const ACTIVE_PROFILE: &str = "activeProfile";
+pub const PLAYER: &str = "player";
+pub const CUSTOM_PLAYER_COMMAND: &str = "customPlayerCommand";
+pub const AUDIO_LANGUAGES: &str = "audioLanguages";
+pub const SUBTITLE_LANGUAGES: &str = "subtitleLanguages";
+pub const PLAYBACK_RETRIES: &str = "playbackRetries";
+pub const PLAYBACK_RETRY_DELAY: &str = "playbackRetryDelay";
 
 pub fn get_settings() -> Result<Settings> {
     let map = sql::get_settings()?;
@@ -39,6 +52,20 @@ pub fn get_settings() -> Result<Settings> {
         enable_hwdec: map.get(ENABLE_HWDEC).and_then(|s| s.parse().ok()),
         always_ask_save: map.get(ALWAYS_ASK_SAVE).and_then(|s| s.parse().ok()),
         enable_gpu: map.get(ENABLE_GPU).and_then(|s| s.parse().ok()),
+        merge_duplicates: map.get(MERGE_DUPLICATES).and_then(|s| s.parse().ok()),
+        default_proxy: map.get(DEFAULT_PROXY).map(|s| s.to_string()),
+        page_size: map.get(PAGE_SIZE).and_then(|s| s.parse().ok()),
+        parental_auto_lock: map.get(PARENTAL_AUTO_LOCK).and_then(|s| s.parse().ok()),
+        parental_keywords: map.get(PARENTAL_KEYWORDS).map(|s| s.to_string()),
+        parental_unlock_minutes: map
+            .get(PARENTAL_UNLOCK_MINUTES)
+            .and_then(|s| s.parse().ok()),
+        player: map.get(PLAYER).and_then(|s| s.parse().ok()),
+        custom_player_command: map.get(CUSTOM_PLAYER_COMMAND).map(|s| s.to_string()),
+        audio_languages: map.get(AUDIO_LANGUAGES).map(|s| s.to_string()),
+        subtitle_languages: map.get(SUBTITLE_LANGUAGES).map(|s| s.to_string()),
+        playback_retries: map.get(PLAYBACK_RETRIES).and_then(|s| s.parse().ok()),
+        playback_retry_delay: map.get(PLAYBACK_RETRY_DELAY).and_then(|s| s.parse().ok()),
     };
     Ok(settings)
 }
@@ -47,6 +74,12 @@ pub fn update_settings(settings: Settings) -> Result<()> {
     let mut map: HashMap<String, Option<String>> = HashMap::with_capacity(13);
 
     map.insert(MPV_PARAMS.to_string(), settings.mpv_params);
+    map.insert(
+        CUSTOM_PLAYER_COMMAND.to_string(),
+        settings.custom_player_command,
+    );
+    map.insert(AUDIO_LANGUAGES.to_string(), settings.audio_languages);
+    map.insert(SUBTITLE_LANGUAGES.to_string(), settings.subtitle_languages);
 
     if let Some(recording_path) = settings.recording_path {
         map.insert(RECORDING_PATH.to_string(), Some(recording_path));
@@ -90,6 +123,36 @@ pub fn update_settings(settings: Settings) -> Result<()> {
     if let Some(gpu) = settings.enable_gpu {
         map.insert(ENABLE_GPU.to_string(), Some(gpu.to_string()));
     }
+    if let Some(merge) = settings.merge_duplicates {
+        map.insert(MERGE_DUPLICATES.to_string(), Some(merge.to_string()));
+    }
+    if let Some(proxy) = settings.default_proxy {
+        map.insert(DEFAULT_PROXY.to_string(), Some(proxy));
+    }
+    if let Some(page_size) = settings.page_size {
+        map.insert(PAGE_SIZE.to_string(), Some(page_size.to_string()));
+    }
+    if let Some(auto_lock) = settings.parental_auto_lock {
+        map.insert(PARENTAL_AUTO_LOCK.to_string(), Some(auto_lock.to_string()));
+    }
+    if let Some(keywords) = settings.parental_keywords {
+        map.insert(PARENTAL_KEYWORDS.to_string(), Some(keywords));
+    }
+    if let Some(minutes) = settings.parental_unlock_minutes {
+        map.insert(
+            PARENTAL_UNLOCK_MINUTES.to_string(),
+            Some(minutes.to_string()),
+        );
+    }
+    if let Some(player) = settings.player {
+        map.insert(PLAYER.to_string(), Some(player.to_string()));
+    }
+    if let Some(retries) = settings.playback_retries {
+        map.insert(PLAYBACK_RETRIES.to_string(), Some(retries.to_string()));
+    }
+    if let Some(delay) = settings.playback_retry_delay {
+        map.insert(PLAYBACK_RETRY_DELAY.to_string(), Some(delay.to_string()));
+    }
     sql::update_settings(map)?;
     Ok(())
 }
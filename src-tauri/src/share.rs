@@ -1,19 +1,47 @@
 use crate::types::CustomChannel;
 use crate::types::ExportedGroup;
+use crate::types::ExportedSavedSearch;
 use crate::types::ExportedSource;
 use crate::types::Group;
 use crate::types::Source;
 use crate::utils::serialize_to_file;
-use crate::{sql, types::Channel};
+use crate::{m3u, sql, types::Channel};
 use anyhow::Context;
 use anyhow::Result;
 use anyhow::bail;
 
-pub fn share_custom_channel(channel: Channel, path: String) -> Result<()> {
-    let channel = get_custom_channel(channel)?;
+pub fn share_custom_channel(
+    channel: Channel,
+    path: String,
+    inline_headers: Option<bool>,
+) -> Result<()> {
+    let mut channel = get_custom_channel(channel)?;
+    if inline_headers.unwrap_or(false) {
+        inline_url_headers(&mut channel);
+    }
     serialize_to_file(channel, path)
 }
 
+fn inline_url_headers(channel: &mut CustomChannel) {
+    if let Some(url) = channel.data.url.take() {
+        let (url, headers) = m3u::join_url_headers(url, channel.headers.take());
+        channel.data.url = Some(url);
+        channel.headers = headers;
+    }
+}
+
+fn get_custom_channels(
+    group_id: Option<i64>,
+    source_id: i64,
+    inline_headers: Option<bool>,
+) -> Result<Vec<CustomChannel>> {
+    let mut channels = sql::get_custom_channels(group_id, source_id)?;
+    if inline_headers.unwrap_or(false) {
+        channels.iter_mut().for_each(inline_url_headers);
+    }
+    Ok(channels)
+}
+
 fn get_custom_channel(channel: Channel) -> Result<CustomChannel> {
     Ok(CustomChannel {
         headers: sql::get_channel_headers_by_id(channel.id.context("No id on channel?")?)?,
@@ -21,7 +49,11 @@ fn get_custom_channel(channel: Channel) -> Result<CustomChannel> {
     })
 }
 
-pub fn share_custom_group(group: Channel, path: String) -> Result<()> {
+pub fn share_custom_group(
+    group: Channel,
+    path: String,
+    inline_headers: Option<bool>,
+) -> Result<()> {
     let to_export = ExportedGroup {
         group: Group {
             id: group.id,
@@ -30,23 +62,66 @@ pub fn share_custom_group(group: Channel, path: String) -> Result<()> {
             source_id: None,
             hidden: Some(false),
         },
-        channels: sql::get_custom_channels(group.id, group.source_id.context("no source id?")?)?,
+        channels: get_custom_channels(
+            group.id,
+            group.source_id.context("no source id?")?,
+            inline_headers,
+        )?,
     };
     serialize_to_file(to_export, path)
 }
 
-pub fn share_custom_source(mut source: Source, path: String) -> Result<()> {
+pub fn share_custom_source(
+    mut source: Source,
+    path: String,
+    inline_headers: Option<bool>,
+) -> Result<()> {
     let id = source.id.context("No source id?")?.clone();
     source.id = None;
+    source.password = None;
     let to_export = ExportedSource {
         source,
-        groups: sql::get_custom_groups(id)?,
-        channels: sql::get_custom_channels(None, id)?,
+        groups: sql::get_custom_groups(id)?
+            .into_iter()
+            .map(|mut group| {
+                if inline_headers.unwrap_or(false) {
+                    group.channels.iter_mut().for_each(inline_url_headers);
+                }
+                group
+            })
+            .collect(),
+        channels: get_custom_channels(None, id, inline_headers)?,
     };
     serialize_to_file(to_export, path)?;
     Ok(())
 }
 
+pub fn share_saved_search(id: i64, path: String) -> Result<()> {
+    let mut search = sql::get_saved_search(id)?;
+    let sources = search
+        .filters
+        .source_ids
+        .iter()
+        .filter_map(|id| sql::get_source_from_id(*id).ok())
+        .map(|source| source.name)
+        .collect();
+    let group = match search.filters.group_id {
+        Some(id) => sql::get_group_by_id(id)?.map(|group| group.name),
+        None => None,
+    };
+    search.id = None;
+    search.filters.source_ids = Vec::new();
+    search.filters.group_id = None;
+    search.filters.series_id = None;
+    search.filters.season = None;
+    let to_export = ExportedSavedSearch {
+        search,
+        sources,
+        group,
+    };
+    serialize_to_file(to_export, path)
+}
+
 pub fn import(path: String, source_id: Option<i64>, name_override: Option<String>) -> Result<()> {
     let data = std::fs::read_to_string(&path)?;
     match path
@@ -57,6 +132,7 @@ pub fn import(path: String, source_id: Option<i64>, name_override: Option<String
         "otv" => import_channel(data, source_id.context("No source id")?, name_override),
         "otvg" => import_group(data, source_id.context("No source id")?, name_override),
         "otvp" => import_playlist(data, name_override),
+        "otvs" => import_saved_search(data, name_override),
         _ => Err(anyhow::anyhow!("Invalid path")),
     }
 }
@@ -104,6 +180,33 @@ fn import_group(data: String, source_id: i64, name_override: Option<String>) ->
     Ok(())
 }
 
+fn import_saved_search(data: String, name_override: Option<String>) -> Result<()> {
+    let mut data: ExportedSavedSearch = serde_json::from_str(&data)?;
+    if let Some(name) = name_override {
+        data.search.name = name;
+    }
+    if sql::saved_search_exists(&data.search.name)? {
+        bail!("Duplicate exists");
+    }
+    let source_ids: Vec<i64> = sql::get_sources()?
+        .into_iter()
+        .filter(|source| data.sources.contains(&source.name))
+        .filter_map(|source| source.id)
+        .collect();
+    if source_ids.is_empty() {
+        bail!("None of the sources used by this saved search exist");
+    }
+    if let Some(group) = data.group.as_ref() {
+        data.search.filters.group_id = Some(
+            sql::get_group_id_by_name(group, &source_ids)?
+                .with_context(|| format!("Group {group} not found"))?,
+        );
+    }
+    data.search.filters.source_ids = source_ids;
+    data.search.id = None;
+    sql::save_search(data.search)
+}
+
 fn import_playlist(data: String, name_override: Option<String>) -> Result<()> {
     let mut data: ExportedSource = serde_json::from_str(&data)?;
     if let Some(name) = name_override {
@@ -0,0 +1,123 @@
+use std::{sync::atomic::Ordering, time::Duration};
+
+use anyhow::{Context, Result};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    log::log,
+    mpv_ipc,
+    types::{AppState, Channel},
+    xtream,
+};
+
+const SLEEP_TIMER_EVENT: &str = "sleep_timer_fired";
+const FADE_STEPS: u32 = 30;
+const FADE_STEP: Duration = Duration::from_secs(1);
+
+pub async fn start(
+    minutes: u32,
+    fade_out: bool,
+    state: State<'_, Mutex<AppState>>,
+    app: AppHandle,
+) -> Result<i64> {
+    let ends_at = chrono::Utc::now().timestamp() + minutes as i64 * 60;
+    schedule(ends_at, fade_out, state, app).await
+}
+
+pub async fn start_at_programme_end(
+    channel: Channel,
+    fade_out: bool,
+    state: State<'_, Mutex<AppState>>,
+    app: AppHandle,
+) -> Result<i64> {
+    let programme = xtream::get_epg(channel)
+        .await?
+        .into_iter()
+        .find(|epg| epg.now_playing)
+        .context("No programme is airing on this channel")?;
+    schedule(programme.end_timestamp, fade_out, state, app).await
+}
+
+pub async fn get(state: State<'_, Mutex<AppState>>) -> Option<i64> {
+    state.lock().await.sleep_timer_end
+}
+
+pub async fn cancel(state: State<'_, Mutex<AppState>>) -> Result<()> {
+    let mut state = state.lock().await;
+    let token = state
+        .sleep_timer_stop
+        .take()
+        .context("no sleep timer running")?;
+    state.sleep_timer_end = None;
+    token.cancel();
+    Ok(())
+}
+
+async fn schedule(
+    ends_at: i64,
+    fade_out: bool,
+    state: State<'_, Mutex<AppState>>,
+    app: AppHandle,
+) -> Result<i64> {
+    let token = CancellationToken::new();
+    {
+        let mut state = state.lock().await;
+        if let Some(previous) = state.sleep_timer_stop.replace(token.clone()) {
+            previous.cancel();
+        }
+        state.sleep_timer_end = Some(ends_at);
+    }
+    let mut delay = Duration::from_secs((ends_at - chrono::Utc::now().timestamp()).max(0) as u64);
+    if fade_out {
+        delay = delay.saturating_sub(FADE_STEP * FADE_STEPS);
+    }
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {},
+            _ = token.cancelled() => return,
+        }
+        if fade_out {
+            fade(&token).await;
+        }
+        if token.is_cancelled() {
+            return;
+        }
+        stop_playback(&app).await;
+    });
+    Ok(ends_at)
+}
+
+/// Lowers mpv's volume step by step, restoring it if the timer gets cancelled midway
+async fn fade(token: &CancellationToken) {
+    let volume = mpv_ipc::get_property("volume".to_string())
+        .await
+        .ok()
+        .and_then(|v| v.as_f64());
+    for step in 1..=FADE_STEPS {
+        if token.is_cancelled() {
+            if let Some(volume) = volume {
+                _ = mpv_ipc::set_volume(volume as u8).await;
+            }
+            return;
+        }
+        if let Some(volume) = volume {
+            let remaining = 1.0 - step as f64 / FADE_STEPS as f64;
+            _ = mpv_ipc::set_volume((volume * remaining) as u8).await;
+        }
+        tokio::time::sleep(FADE_STEP).await;
+    }
+}
+
+async fn stop_playback(app: &AppHandle) {
+    let state = app.state::<Mutex<AppState>>();
+    let mut state = state.lock().await;
+    // Every player's token is a child of this one, downloads are left running
+    std::mem::take(&mut state.playback_stop).cancel();
+    state.restream_stop_signal.store(true, Ordering::Relaxed);
+    state.sleep_timer_stop = None;
+    state.sleep_timer_end = None;
+    log("Sleep timer stopped playback".to_string());
+    let _ = app.emit(SLEEP_TIMER_EVENT, true);
+}
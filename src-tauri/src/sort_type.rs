@@ -1,3 +1,5 @@
 pub const ALPHABETICAL_ASC: u8 = 0;
 pub const ALPHABETICAL_DESC: u8 = 1;
 pub const PROVIDER: u8 = 2;
+pub const RELEVANCE: u8 = 3;
+pub const CUSTOM: u8 = 4;
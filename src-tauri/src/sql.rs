@@ -1,24 +1,63 @@
 use std::{collections::HashMap, sync::LazyLock};
 
+use crate::crypto;
 use crate::log::log;
+use crate::logical;
+use crate::name_rules::NameRewriter;
+use crate::query::{self, CHANNELS_FTS, GROUPS_FTS, SEASONS_FTS, SearchQuery, SearchTable};
+use crate::relevance;
 use crate::sort_type;
 use crate::types::{
     ChannelPreserve, CustomChannel, CustomChannelExtraData, EPGNotify, ExportedGroup, Group,
-    IdName, Season,
+    HttpHeader, IdName, NameRule, PlaybackOverrides, Profile, SavedSearch, SearchCursor,
+    SearchPage, Season, UserList,
 };
 use crate::{
-    media_type, source_type,
-    types::{Channel, ChannelHttpHeaders, Filters, Source},
-    view_type,
+    m3u, media_type, parental, settings, source_type,
+    types::{Channel, ChannelDrm, ChannelHttpHeaders, Filters, Source},
+    view_type, xtream,
 };
-use anyhow::{Context, Result, anyhow};
-use directories::ProjectDirs;
+use anyhow::{Context, Result, anyhow, bail};
 use r2d2::{Pool, PooledConnection};
 use r2d2_sqlite::SqliteConnectionManager;
 use rusqlite::{OptionalExtension, Row, Transaction, params, params_from_iter};
 use rusqlite_migration::{M, Migrations};
 
-const PAGE_SIZE: u8 = 36;
+const PAGE_SIZE: u16 = 36;
+const COMPLETED_RATIO: f64 = 0.95;
+const MIN_PROGRESS_SECONDS: f64 = 30.0;
+const DEFAULT_PROFILE: i64 = 1;
+const MAX_PAGE_SIZE: u16 = 500;
+const LIST_MEMBER_SQL: &str = r#"EXISTS (
+    SELECT 1 FROM list_entries e
+    WHERE e.list_id = ?
+    AND e.source_id = channels.source_id
+    AND e.channel_name = COALESCE(channels.original_name, channels.name)
+)"#;
+const LIST_ORDER_SQL: &str = r#"
+ORDER BY (
+    SELECT e.position FROM list_entries e
+    WHERE e.list_id = ?
+    AND e.source_id = channels.source_id
+    AND e.channel_name = COALESCE(channels.original_name, channels.name)
+), name ASC"#;
+const CUSTOM_ORDER_SQL: &str = "\nORDER BY position IS NULL, position ASC, name ASC";
+const CHANNEL_LOCK_SQL: &str = r#"
+AND locked = 0
+AND source_id NOT IN (SELECT id FROM sources WHERE locked = 1)
+AND (group_id IS NULL OR group_id NOT IN (SELECT id FROM groups WHERE locked = 1))"#;
+const GROUP_LOCK_SQL: &str = r#"
+AND locked = 0
+AND source_id NOT IN (SELECT id FROM sources WHERE locked = 1)"#;
+const BULK_CONDITIONS: &str = "{conditions}";
+const RELEVANCE_ORDER_SQL: &str = "\nORDER BY relevance(name, ?), name ASC";
+// Dead after 3 failed checks in a row, a single failure is often just a provider hiccup
+const DEAD_CHANNEL_SQL: &str = r#"EXISTS (
+    SELECT 1 FROM channel_health h
+    WHERE h.source_id = channels.source_id
+    AND h.url = channels.url
+    AND h.failures >= 3
+)"#;
 pub const DB_NAME: &str = "db.sqlite";
 static CONN: LazyLock<Pool<SqliteConnectionManager>> = LazyLock::new(|| create_connection_pool());
 
@@ -27,12 +66,14 @@ pub fn get_conn() -> Result<PooledConnection<SqliteConnectionManager>> {
 }
 
 fn create_connection_pool() -> Pool<SqliteConnectionManager> {
-    let manager = SqliteConnectionManager::file(get_and_create_sqlite_db_path());
+    let manager = SqliteConnectionManager::file(get_and_create_sqlite_db_path())
+        .with_init(|conn| relevance::register_functions(conn));
     r2d2::Pool::builder().max_size(20).build(manager).unwrap()
 }
 
+#[cfg(not(test))]
 fn get_and_create_sqlite_db_path() -> String {
-    let mut path = ProjectDirs::from("dev", "fredol", "open-tv")
+    let mut path = directories::ProjectDirs::from("dev", "fredol", "open-tv")
         .unwrap()
         .data_dir()
         .to_owned();
@@ -43,6 +84,13 @@ fn get_and_create_sqlite_db_path() -> String {
     return path.to_string_lossy().to_string();
 }
 
+#[cfg(test)]
+fn get_and_create_sqlite_db_path() -> String {
+    let path = std::env::temp_dir().join(format!("open-tv-test-{}.sqlite", std::process::id()));
+    _ = std::fs::remove_file(&path);
+    path.to_string_lossy().to_string()
+}
+
 fn create_structure() -> Result<()> {
     let sql = get_conn()?;
     sql.execute_batch(
@@ -128,6 +176,7 @@ pub fn create_or_initialize_db() -> Result<()> {
 
 fn apply_migrations() -> Result<()> {
     let mut sql = get_conn()?;
+    let fts_migration = get_fts_migration();
     let migrations = Migrations::new(vec![
         M::up(
             r#"
@@ -233,11 +282,219 @@ fn apply_migrations() -> Result<()> {
               ANALYZE;
             "#,
         ),
+        M::up(
+            r#"
+              ALTER TABLE channels ADD COLUMN original_name varchar(100);
+              CREATE INDEX index_channels_original_name ON channels(original_name);
+              CREATE TABLE IF NOT EXISTS "name_rules" (
+                "id" INTEGER PRIMARY KEY,
+                "source_id" integer,
+                "rule_type" integer,
+                "pattern" varchar(500),
+                "replacement" varchar(500),
+                FOREIGN KEY (source_id) REFERENCES sources(id) ON DELETE CASCADE
+              );
+              CREATE INDEX index_name_rules_source_id ON name_rules(source_id);
+            "#,
+        ),
+        M::up(
+            r#"
+              ALTER TABLE channels ADD COLUMN tvg_id varchar(100);
+              ALTER TABLE channels ADD COLUMN merge_key varchar(100);
+              CREATE INDEX index_channels_merge_key ON channels(merge_key);
+            "#,
+        ),
+        M::up(
+            r#"
+              CREATE TABLE IF NOT EXISTS "channel_health" (
+                "id" INTEGER PRIMARY KEY,
+                "source_id" integer,
+                "url" varchar(500),
+                "last_checked" integer,
+                "last_ok" integer,
+                "failures" integer DEFAULT 0,
+                FOREIGN KEY (source_id) REFERENCES sources(id) ON DELETE CASCADE,
+                UNIQUE(source_id, url)
+              );
+              CREATE INDEX index_channel_health_failures ON channel_health(failures);
+            "#,
+        ),
+        M::up(
+            r#"
+              ALTER TABLE sources ADD COLUMN proxy varchar(500);
+            "#,
+        ),
+        M::up(
+            r#"
+              ALTER TABLE channel_http_headers ADD COLUMN cookies varchar(2000);
+              CREATE TABLE IF NOT EXISTS "extra_http_headers" (
+                "id" INTEGER PRIMARY KEY,
+                "channel_id" integer,
+                "source_id" integer,
+                "name" varchar(200),
+                "value" varchar(2000)
+              );
+              CREATE INDEX index_extra_http_headers_channel_id ON extra_http_headers(channel_id);
+              CREATE INDEX index_extra_http_headers_source_id ON extra_http_headers(source_id);
+            "#,
+        ),
+        M::up(
+            r#"
+              CREATE TABLE IF NOT EXISTS "channel_drm" (
+                "id" INTEGER PRIMARY KEY,
+                "channel_id" integer,
+                "source_id" integer,
+                "license_type" varchar(100),
+                "license_key" varchar(2000)
+              );
+              CREATE UNIQUE INDEX index_channel_drm_channel_id ON channel_drm(channel_id);
+              CREATE INDEX index_channel_drm_source_id ON channel_drm(source_id);
+            "#,
+        ),
+        M::up(&fts_migration),
+        M::up(
+            r#"
+              CREATE TABLE IF NOT EXISTS "saved_searches" (
+                "id" INTEGER PRIMARY KEY,
+                "name" varchar(100) NOT NULL,
+                "filters" text NOT NULL
+              );
+              CREATE UNIQUE INDEX index_saved_searches_name ON saved_searches(name);
+            "#,
+        ),
+        M::up(
+            r#"
+              CREATE TABLE IF NOT EXISTS "lists" (
+                "id" INTEGER PRIMARY KEY,
+                "name" varchar(100) NOT NULL
+              );
+              CREATE UNIQUE INDEX index_lists_name ON lists(name);
+              CREATE TABLE IF NOT EXISTS "list_entries" (
+                "id" INTEGER PRIMARY KEY,
+                "list_id" integer NOT NULL,
+                "source_id" integer NOT NULL,
+                "channel_name" varchar(500) NOT NULL,
+                "position" integer NOT NULL DEFAULT 0
+              );
+              CREATE UNIQUE INDEX index_list_entries_unique ON list_entries(list_id, source_id, channel_name);
+              CREATE INDEX index_list_entries_source_id ON list_entries(source_id);
+            "#,
+        ),
+        M::up(
+            r#"
+              ALTER TABLE channels ADD COLUMN position integer;
+              ALTER TABLE groups ADD COLUMN position integer;
+            "#,
+        ),
+        M::up(
+            r#"
+              ALTER TABLE channels ADD COLUMN locked integer NOT NULL DEFAULT 0;
+              ALTER TABLE groups ADD COLUMN locked integer NOT NULL DEFAULT 0;
+              ALTER TABLE sources ADD COLUMN locked integer NOT NULL DEFAULT 0;
+            "#,
+        ),
+        M::up(
+            r#"
+              CREATE TABLE IF NOT EXISTS "profiles" (
+                "id" INTEGER PRIMARY KEY,
+                "name" varchar(100) NOT NULL,
+                "volume" integer,
+                "default_view" integer
+              );
+              CREATE UNIQUE INDEX index_profiles_name ON profiles(name);
+              INSERT INTO profiles (id, name) VALUES (1, 'Default');
+              CREATE TABLE IF NOT EXISTS "profile_state" (
+                "id" INTEGER PRIMARY KEY,
+                "profile_id" integer NOT NULL,
+                "source_id" integer NOT NULL,
+                "name" varchar(500) NOT NULL,
+                "is_group" integer NOT NULL DEFAULT 0,
+                "favorite" integer NOT NULL DEFAULT 0,
+                "last_watched" integer,
+                "hidden" integer NOT NULL DEFAULT 0
+              );
+              CREATE INDEX index_profile_state_profile_id ON profile_state(profile_id);
+              CREATE INDEX index_profile_state_source_id ON profile_state(source_id);
+              ALTER TABLE lists ADD COLUMN profile_id integer NOT NULL DEFAULT 1;
+              DROP INDEX index_lists_name;
+              CREATE UNIQUE INDEX index_lists_name ON lists(profile_id, name);
+            "#,
+        ),
+        M::up(
+            r#"
+              CREATE TABLE IF NOT EXISTS "watch_progress" (
+                "id" INTEGER PRIMARY KEY,
+                "profile_id" integer NOT NULL,
+                "source_id" integer NOT NULL,
+                "channel_name" varchar(500) NOT NULL,
+                "position" real NOT NULL,
+                "duration" real NOT NULL,
+                "updated_at" integer NOT NULL
+              );
+              CREATE UNIQUE INDEX index_watch_progress_unique ON watch_progress(profile_id, source_id, channel_name);
+              CREATE INDEX index_watch_progress_source_id ON watch_progress(source_id);
+            "#,
+        ),
+        M::up(
+            r#"
+              ALTER TABLE sources ADD COLUMN player integer;
+            "#,
+        ),
+        M::up(
+            r#"
+              ALTER TABLE channels ADD COLUMN playback_overrides text;
+              ALTER TABLE sources ADD COLUMN playback_overrides text;
+            "#,
+        ),
+        M::up(
+            r#"
+              ALTER TABLE profiles ADD COLUMN audio_languages varchar(100);
+              ALTER TABLE profiles ADD COLUMN subtitle_languages varchar(100);
+            "#,
+        ),
+        M::up(
+            r#"
+              ALTER TABLE sources ADD COLUMN mirrors varchar(2000);
+            "#,
+        ),
     ]);
     migrations.to_latest(&mut sql)?;
     Ok(())
 }
 
+fn get_fts_migration() -> String {
+    [
+        (CHANNELS_FTS, "channels"),
+        (GROUPS_FTS, "groups"),
+        (SEASONS_FTS, "seasons"),
+    ]
+    .iter()
+    .map(|(fts, table)| {
+        format!(
+            r#"
+                CREATE VIRTUAL TABLE IF NOT EXISTS {fts} USING fts5(
+                    name,
+                    content='{table}',
+                    content_rowid='id',
+                    tokenize='unicode61 remove_diacritics 2'
+                );
+                CREATE TRIGGER IF NOT EXISTS {fts}_insert AFTER INSERT ON {table} BEGIN
+                    INSERT INTO {fts}(rowid, name) VALUES (new.id, new.name);
+                END;
+                CREATE TRIGGER IF NOT EXISTS {fts}_delete AFTER DELETE ON {table} BEGIN
+                    INSERT INTO {fts}({fts}, rowid, name) VALUES ('delete', old.id, old.name);
+                END;
+                CREATE TRIGGER IF NOT EXISTS {fts}_update AFTER UPDATE OF name ON {table} BEGIN
+                    INSERT INTO {fts}({fts}, rowid, name) VALUES ('delete', old.id, old.name);
+                    INSERT INTO {fts}(rowid, name) VALUES (new.id, new.name);
+                END;
+                INSERT INTO {fts}({fts}) VALUES ('rebuild');
+            "#
+        )
+    })
+    .collect()
+}
+
 pub fn drop_db() -> Result<()> {
     let sql = get_conn()?;
     sql.execute_batch(
@@ -258,8 +515,8 @@ pub fn create_or_find_source_by_name(tx: &Transaction, source: &Source) -> Resul
         return Ok(id);
     }
     tx.execute(
-    "INSERT INTO sources (name, source_type, url, username, password, use_tvg_id, user_agent, max_streams, last_updated) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
-    params![source.name, source.source_type.clone() as u8, source.url, source.username, source.password, source.use_tvg_id, source.user_agent, source.max_streams, chrono::Utc::now().timestamp()],
+    "INSERT INTO sources (name, source_type, url, username, password, use_tvg_id, user_agent, max_streams, last_updated, proxy, player, mirrors) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    params![source.name, source.source_type.clone() as u8, source.url, source.username, crypto::encrypt_password(source.password.clone())?, source.use_tvg_id, source.user_agent, source.max_streams, chrono::Utc::now().timestamp(), source.proxy, source.player, source.mirrors],
     )?;
     Ok(tx.last_insert_rowid())
 }
@@ -296,10 +553,11 @@ pub fn insert_season(tx: &Transaction, season: Season) -> Result<i64> {
 }
 
 pub fn insert_channel(tx: &Transaction, channel: Channel) -> Result<()> {
+    let merge_key = logical::get_merge_key(&channel);
     tx.execute(
         r#"
-INSERT INTO channels (name, group_id, image, url, source_id, media_type, series_id, favorite, stream_id, tv_archive, season_id, episode_num)
-VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+INSERT INTO channels (name, group_id, image, url, source_id, media_type, series_id, favorite, stream_id, tv_archive, season_id, episode_num, original_name, tvg_id, merge_key)
+VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
 ON CONFLICT (name, source_id, url, series_id, season_id)
 DO UPDATE SET
     url = excluded.url,
@@ -308,7 +566,10 @@ DO UPDATE SET
     image = excluded.image,
     series_id = excluded.series_id,
     tv_archive = excluded.tv_archive,
-    season_id = excluded.season_id;
+    season_id = excluded.season_id,
+    original_name = excluded.original_name,
+    tvg_id = excluded.tvg_id,
+    merge_key = excluded.merge_key;
 "#,
         params![
             channel.name,
@@ -322,7 +583,10 @@ DO UPDATE SET
             channel.stream_id,
             channel.tv_archive,
             channel.season_id,
-            channel.episode_num
+            channel.episode_num,
+            channel.original_name,
+            channel.tvg_id,
+            merge_key
         ],
     )?;
     Ok(())
@@ -331,17 +595,180 @@ DO UPDATE SET
 pub fn insert_channel_headers(tx: &Transaction, headers: ChannelHttpHeaders) -> Result<()> {
     tx.execute(
         r#"
-INSERT OR IGNORE INTO channel_http_headers (channel_id, referrer, user_agent, http_origin, ignore_ssl)
-VALUES (?, ?, ?, ?, ?);
+INSERT OR IGNORE INTO channel_http_headers (channel_id, referrer, user_agent, http_origin, ignore_ssl, cookies)
+VALUES (?, ?, ?, ?, ?, ?);
 "#,
         params![
             headers.channel_id,
             headers.referrer,
             headers.user_agent,
             headers.http_origin,
-            headers.ignore_ssl
+            headers.ignore_ssl,
+            headers.cookies
         ],
     )?;
+    set_channel_extra_headers(
+        tx,
+        headers.channel_id.context("no channel id")?,
+        headers.extra_headers.unwrap_or_default(),
+    )
+}
+
+pub fn insert_channel_drm(tx: &Transaction, drm: ChannelDrm) -> Result<()> {
+    tx.execute(
+        r#"
+        INSERT OR IGNORE INTO channel_drm (channel_id, source_id, license_type, license_key)
+        SELECT id, source_id, ?, ? FROM channels WHERE id = ?
+    "#,
+        params![drm.license_type, drm.license_key, drm.channel_id],
+    )?;
+    Ok(())
+}
+
+pub fn get_channel_drm(channel_id: i64) -> Result<Option<ChannelDrm>> {
+    let sql = get_conn()?;
+    Ok(sql
+        .query_row(
+            "SELECT channel_id, license_type, license_key FROM channel_drm WHERE channel_id = ?",
+            params![channel_id],
+            |row| {
+                Ok(ChannelDrm {
+                    channel_id: row.get("channel_id")?,
+                    license_type: row.get("license_type")?,
+                    license_key: row.get("license_key")?,
+                })
+            },
+        )
+        .optional()?)
+}
+
+fn get_playback_overrides(table: &str, id: i64) -> Result<Option<PlaybackOverrides>> {
+    let sql = get_conn()?;
+    let overrides: Option<String> = sql
+        .query_row(
+            &format!("SELECT playback_overrides FROM {table} WHERE id = ?"),
+            params![id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .flatten();
+    Ok(overrides
+        .map(|overrides| serde_json::from_str(&overrides))
+        .transpose()?)
+}
+
+fn set_playback_overrides(
+    table: &str,
+    id: i64,
+    overrides: Option<PlaybackOverrides>,
+) -> Result<()> {
+    let overrides = overrides
+        .filter(|overrides| *overrides != PlaybackOverrides::default())
+        .map(|overrides| serde_json::to_string(&overrides))
+        .transpose()?;
+    let sql = get_conn()?;
+    sql.execute(
+        &format!("UPDATE {table} SET playback_overrides = ? WHERE id = ?"),
+        params![overrides, id],
+    )?;
+    Ok(())
+}
+
+pub fn get_channel_playback_overrides(channel_id: i64) -> Result<Option<PlaybackOverrides>> {
+    get_playback_overrides("channels", channel_id)
+}
+
+pub fn set_channel_playback_overrides(
+    channel_id: i64,
+    overrides: Option<PlaybackOverrides>,
+) -> Result<()> {
+    set_playback_overrides("channels", channel_id, overrides)
+}
+
+pub fn get_source_playback_overrides(source_id: i64) -> Result<Option<PlaybackOverrides>> {
+    get_playback_overrides("sources", source_id)
+}
+
+pub fn set_source_playback_overrides(
+    source_id: i64,
+    overrides: Option<PlaybackOverrides>,
+) -> Result<()> {
+    set_playback_overrides("sources", source_id, overrides)
+}
+
+fn set_channel_extra_headers(
+    tx: &Transaction,
+    channel_id: i64,
+    headers: Vec<HttpHeader>,
+) -> Result<()> {
+    tx.execute(
+        "DELETE FROM extra_http_headers WHERE channel_id = ?",
+        params![channel_id],
+    )?;
+    for header in headers {
+        tx.execute(
+            r#"
+            INSERT INTO extra_http_headers (channel_id, source_id, name, value)
+            SELECT ?1, source_id, ?2, ?3 FROM channels WHERE id = ?1
+        "#,
+            params![channel_id, header.name, header.value],
+        )?;
+    }
+    Ok(())
+}
+
+fn get_channel_extra_headers(
+    sql: &rusqlite::Connection,
+    channel_id: i64,
+) -> Result<Option<Vec<HttpHeader>>> {
+    let headers: Vec<HttpHeader> = sql
+        .prepare("SELECT name, value FROM extra_http_headers WHERE channel_id = ? ORDER BY id")?
+        .query_map(params![channel_id], row_to_http_header)?
+        .filter_map(Result::ok)
+        .collect();
+    Ok((!headers.is_empty()).then_some(headers))
+}
+
+fn row_to_http_header(row: &Row) -> Result<HttpHeader, rusqlite::Error> {
+    Ok(HttpHeader {
+        name: row.get("name")?,
+        value: row.get("value")?,
+    })
+}
+
+pub fn get_source_headers(source_id: i64) -> Result<Vec<HttpHeader>> {
+    let sql = get_conn()?;
+    let headers = sql
+        .prepare(
+            r#"
+            SELECT name, value
+            FROM extra_http_headers
+            WHERE source_id = ?
+            AND channel_id IS NULL
+            ORDER BY id
+        "#,
+        )?
+        .query_map(params![source_id], row_to_http_header)?
+        .filter_map(Result::ok)
+        .collect();
+    Ok(headers)
+}
+
+pub fn set_source_headers(
+    tx: &Transaction,
+    source_id: i64,
+    headers: Vec<HttpHeader>,
+) -> Result<()> {
+    tx.execute(
+        "DELETE FROM extra_http_headers WHERE source_id = ? AND channel_id IS NULL",
+        params![source_id],
+    )?;
+    for header in headers {
+        tx.execute(
+            "INSERT INTO extra_http_headers (source_id, name, value) VALUES (?, ?, ?)",
+            params![source_id, header.name, header.value],
+        )?;
+    }
     Ok(())
 }
 
@@ -398,13 +825,16 @@ pub fn set_channel_group_id(
 
 pub fn get_channel_headers_by_id(id: i64) -> Result<Option<ChannelHttpHeaders>> {
     let sql = get_conn()?;
-    let headers = sql
+    let mut headers = sql
         .query_row(
             "SELECT * FROM channel_http_headers WHERE channel_id = ?",
             params![id],
             row_to_channel_headers,
         )
         .optional()?;
+    if let Some(headers) = headers.as_mut() {
+        headers.extra_headers = get_channel_extra_headers(&sql, id)?;
+    }
     Ok(headers)
 }
 
@@ -416,6 +846,8 @@ fn row_to_channel_headers(row: &Row) -> Result<ChannelHttpHeaders, rusqlite::Err
         referrer: row.get("referrer")?,
         user_agent: row.get("user_agent")?,
         ignore_ssl: row.get("ignore_ssl")?,
+        cookies: row.get("cookies")?,
+        extra_headers: None,
     })
 }
 
@@ -451,32 +883,154 @@ pub fn update_settings(map: HashMap<String, Option<String>>) -> Result<()> {
 }
 
 pub fn search(filters: Filters) -> Result<Vec<Channel>> {
+    Ok(search_dispatch(filters, false)?.channels)
+}
+
+pub fn search_page(filters: Filters) -> Result<SearchPage> {
+    search_dispatch(filters, true)
+}
+
+fn search_dispatch(filters: Filters, with_total: bool) -> Result<SearchPage> {
+    if filters.view_type == view_type::SAVED_SEARCHES {
+        return match filters.saved_search_id {
+            Some(id) => search_dispatch(get_saved_search_filters(id, &filters)?, with_total),
+            None => search_named(
+                "saved_searches",
+                media_type::SAVED_SEARCH,
+                "",
+                filters,
+                with_total,
+            ),
+        };
+    }
+    if filters.view_type == view_type::CONTINUE_WATCHING && filters.series_id.is_none() {
+        return search_continue_watching(filters, with_total);
+    }
+    if filters.view_type == view_type::LIST && filters.list_id.is_none() {
+        let condition = format!("AND profile_id = {}", get_active_profile()?);
+        return search_named("lists", media_type::LIST, &condition, filters, with_total);
+    }
     if filters.view_type == view_type::CATEGORIES
         && filters.group_id.is_none()
         && filters.series_id.is_none()
     {
-        return search_group(filters);
+        return search_group(filters, with_total);
     }
     if filters.view_type == view_type::HIDDEN {
-        return search_hidden(filters);
+        return search_hidden(filters, with_total);
     }
     if filters.series_id.is_some() && filters.season.is_none() {
-        return search_series(filters);
+        return search_series(filters, with_total);
+    }
+    search_channels(filters, with_total)
+}
+
+fn get_lock_sql(lock_sql: &'static str) -> Result<&'static str> {
+    Ok(match parental::is_unlocked()? {
+        true => "",
+        false => lock_sql,
+    })
+}
+
+pub fn is_channel_locked(id: i64) -> Result<bool> {
+    let sql = get_conn()?;
+    let locked = sql.query_row(
+        &format!(
+            r#"
+            SELECT EXISTS (SELECT 1 FROM channels WHERE id = ?1)
+            AND NOT EXISTS (SELECT 1 FROM channels WHERE id = ?1 {CHANNEL_LOCK_SQL})
+        "#
+        ),
+        params![id],
+        |row| row.get(0),
+    )?;
+    Ok(locked)
+}
+
+pub fn lock_channel(id: i64, locked: bool) -> Result<()> {
+    let sql = get_conn()?;
+    sql.execute(
+        "UPDATE channels SET locked = ? WHERE id = ?",
+        params![locked, id],
+    )?;
+    Ok(())
+}
+
+pub fn lock_group(id: i64, locked: bool) -> Result<()> {
+    let sql = get_conn()?;
+    sql.execute(
+        "UPDATE groups SET locked = ? WHERE id = ?",
+        params![locked, id],
+    )?;
+    Ok(())
+}
+
+pub fn lock_source(id: i64, locked: bool) -> Result<()> {
+    let sql = get_conn()?;
+    sql.execute(
+        "UPDATE sources SET locked = ? WHERE id = ?",
+        params![locked, id],
+    )?;
+    Ok(())
+}
+
+pub fn lock_groups_by_keywords(tx: &Transaction, source_id: i64, keywords: &[&str]) -> Result<()> {
+    for keyword in keywords {
+        tx.execute(
+            r#"
+            UPDATE groups
+            SET locked = 1
+            WHERE source_id = ?
+            AND name LIKE ?
+        "#,
+            params![source_id, format!("%{keyword}%")],
+        )?;
+    }
+    Ok(())
+}
+
+fn get_page_size(filters: &Filters) -> u16 {
+    filters
+        .page_size
+        .or_else(|| settings::get_settings().ok().and_then(|s| s.page_size))
+        .unwrap_or(PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE)
+}
+
+fn get_offset(filters: &Filters, page_size: u16) -> i64 {
+    (filters.page.max(1) as i64 - 1) * page_size as i64
+}
+
+fn get_total(
+    sql: &rusqlite::Connection,
+    sql_query: &str,
+    params: &[&dyn rusqlite::ToSql],
+    with_total: bool,
+) -> Result<Option<u64>> {
+    if !with_total {
+        return Ok(None);
     }
+    let total = sql.query_row(
+        &format!("SELECT COUNT(*) FROM ({sql_query})"),
+        params_from_iter(params),
+        |row| row.get(0),
+    )?;
+    Ok(Some(total))
+}
+
+fn search_channels(filters: Filters, with_total: bool) -> Result<SearchPage> {
     let sql = get_conn()?;
-    let offset: u16 = filters.page as u16 * PAGE_SIZE as u16 - PAGE_SIZE as u16;
+    let page_size = get_page_size(&filters);
     let media_types = match filters.series_id.is_some() {
         true => vec![1],
         false => filters.media_types.clone().unwrap(),
     };
-    let query = filters.query.unwrap_or("".to_string());
-    let keywords: Vec<String> = match filters.use_keywords {
-        true => query
-            .split(" ")
-            .map(|f| format!("%{f}%").to_string())
-            .collect(),
-        false => vec![format!("%{query}%")],
-    };
+    let search = query::parse(
+        filters.query.as_deref().unwrap_or(""),
+        filters.use_keywords,
+        filters.fuzzy.unwrap_or(false),
+    )?;
+    let query = search.get_text();
     let mut sql_query = format!(
         r#"
         SELECT * FROM CHANNELS
@@ -485,14 +1039,30 @@ pub fn search(filters: Filters) -> Result<Vec<Channel>> {
         AND source_id IN ({})
         AND url IS NOT NULL
         AND hidden = 0"#,
-        get_keywords_sql(keywords.len()),
+        search.to_sql(SearchTable::Channels),
         generate_placeholders(media_types.len()),
         generate_placeholders(filters.source_ids.len()),
     );
+    sql_query += get_lock_sql(CHANNEL_LOCK_SQL)?;
     let mut baked_params = 2;
+    let list_id = get_list_filter(&filters);
+    let merge_duplicates = logical::merge_enabled()
+        && filters.series_id.is_none()
+        && filters.group_id.is_none()
+        && list_id.is_none();
+    if merge_duplicates {
+        sql_query +=
+            &get_merge_duplicates_sql(filters.view_type, &search, filters.source_ids.len());
+    }
     if filters.view_type == view_type::FAVORITES && filters.series_id.is_none() {
         sql_query += "\nAND favorite = 1";
     }
+    if filters.view_type == view_type::HISTORY {
+        sql_query += "\nAND last_watched IS NOT NULL";
+    }
+    if filters.exclude_dead.unwrap_or(false) {
+        sql_query += &format!("\nAND NOT {DEAD_CHANNEL_SQL}");
+    }
 
     if filters.series_id.is_some() {
         sql_query += &format!("\nAND series_id = ?");
@@ -509,21 +1079,59 @@ pub fn search(filters: Filters) -> Result<Vec<Channel>> {
         sort_type::ALPHABETICAL_DESC => "DESC",
         _ => "ASC",
     };
+    let order_by_relevance = filters.sort == sort_type::RELEVANCE
+        && filters.view_type != view_type::HISTORY
+        && filters.season.is_none();
+    let order_by_list = list_id.is_some()
+        && (filters.sort == sort_type::PROVIDER || filters.sort == sort_type::CUSTOM);
+    if list_id.is_some() {
+        sql_query += &format!("\nAND {LIST_MEMBER_SQL}");
+    }
+    let use_keyset = filters.view_type != view_type::HISTORY
+        && filters.season.is_none()
+        && !order_by_list
+        && matches!(
+            filters.sort,
+            sort_type::ALPHABETICAL_ASC | sort_type::ALPHABETICAL_DESC | sort_type::PROVIDER
+        );
+    let cursor = filters.cursor.as_ref().filter(|_| use_keyset);
+    let count_query = sql_query.clone();
+    if cursor.is_some() {
+        sql_query += match filters.sort {
+            sort_type::ALPHABETICAL_ASC => "\nAND (name, id) > (?, ?)",
+            sort_type::ALPHABETICAL_DESC => "\nAND (name, id) < (?, ?)",
+            _ => "\nAND id > ?",
+        };
+    }
     if filters.view_type == view_type::HISTORY {
-        sql_query += "\nAND last_watched IS NOT NULL";
         sql_query += "\nORDER BY last_watched DESC";
     } else if filters.season.is_some() {
         sql_query += &format!("\nORDER BY episode_num {0}, name {0}", order)
-    } else if filters.sort != sort_type::PROVIDER {
-        sql_query += &format!("\nORDER BY name {}", order);
+    } else if order_by_relevance {
+        sql_query += RELEVANCE_ORDER_SQL;
+    } else if order_by_list {
+        sql_query += LIST_ORDER_SQL;
+    } else if filters.sort == sort_type::CUSTOM {
+        sql_query += CUSTOM_ORDER_SQL;
+    } else if filters.sort == sort_type::PROVIDER {
+        sql_query += "\nORDER BY id ASC";
+    } else {
+        sql_query += &format!("\nORDER BY name {0}, id {0}", order);
     }
     sql_query += "\nLIMIT ?, ?";
-    let mut params: Vec<&dyn rusqlite::ToSql> = Vec::with_capacity(
-        baked_params + media_types.len() + filters.source_ids.len() + keywords.len(),
-    );
-    params.extend(to_to_sql(&keywords));
+    let offset = match cursor {
+        Some(_) => 0,
+        None => get_offset(&filters, page_size),
+    };
+    let mut params: Vec<&dyn rusqlite::ToSql> =
+        Vec::with_capacity(baked_params + media_types.len() + filters.source_ids.len());
+    params.extend(search.params(SearchTable::Channels));
     params.extend(to_to_sql(&media_types));
     params.extend(to_to_sql(&filters.source_ids));
+    if merge_duplicates {
+        params.extend(search.params(SearchTable::Channels));
+        params.extend(to_to_sql(&filters.source_ids));
+    }
     if let Some(ref series_id) = filters.series_id {
         params.push(series_id);
     } else if let Some(ref group) = filters.group_id {
@@ -532,27 +1140,75 @@ pub fn search(filters: Filters) -> Result<Vec<Channel>> {
     if let Some(ref season) = filters.season {
         params.push(season);
     }
-    params.push(&offset);
-    params.push(&PAGE_SIZE);
+    if let Some(ref list_id) = list_id {
+        params.push(list_id);
+    }
+    let total = get_total(&sql, &count_query, &params, with_total)?;
+    if let Some(cursor) = cursor {
+        if filters.sort != sort_type::PROVIDER {
+            params.push(&cursor.name);
+        }
+        params.push(&cursor.id);
+    }
+    if order_by_relevance {
+        params.push(&query);
+    } else if order_by_list && let Some(ref list_id) = list_id {
+        params.push(list_id);
+    }
+    params.push(&offset);
+    params.push(&page_size);
     let channels: Vec<Channel> = sql
         .prepare(&sql_query)?
         .query_map(params_from_iter(params), row_to_channel)?
         .filter_map(Result::ok)
         .collect();
-    Ok(channels)
+    let next_cursor = match channels.last() {
+        Some(last) if use_keyset && channels.len() == page_size as usize => Some(SearchCursor {
+            name: last.name.clone(),
+            id: last.id.context("no channel id")?,
+        }),
+        _ => None,
+    };
+    Ok(SearchPage {
+        channels,
+        total,
+        next_cursor,
+    })
 }
 
-fn search_series(filters: Filters) -> Result<Vec<Channel>> {
-    let sql = get_conn()?;
-    let offset: u16 = filters.page as u16 * PAGE_SIZE as u16 - PAGE_SIZE as u16;
-    let query = filters.query.unwrap_or("".to_string());
-    let keywords: Vec<String> = match filters.use_keywords {
-        true => query
-            .split(" ")
-            .map(|f| format!("%{f}%").to_string())
-            .collect(),
-        false => vec![format!("%{query}%")],
+fn get_merge_duplicates_sql(view_type: u8, search: &SearchQuery, sources: usize) -> String {
+    let view_filter = match view_type {
+        view_type::FAVORITES => "\n            AND favorite = 1",
+        view_type::HISTORY => "\n            AND last_watched IS NOT NULL",
+        _ => "",
     };
+    format!(
+        r#"
+        AND (merge_key IS NULL OR NOT EXISTS (
+            SELECT 1 FROM channels d
+            WHERE d.merge_key = channels.merge_key
+            AND d.id < channels.id
+            AND ({})
+            AND d.source_id IN ({})
+            AND d.url IS NOT NULL
+            AND d.hidden = 0{}
+        ))"#,
+        search.to_sql(SearchTable::Channels),
+        generate_placeholders(sources),
+        view_filter
+    )
+}
+
+fn search_series(filters: Filters, with_total: bool) -> Result<SearchPage> {
+    let sql = get_conn()?;
+    let page_size = get_page_size(&filters);
+    let offset = get_offset(&filters, page_size);
+    let search = query::parse(
+        filters.query.as_deref().unwrap_or(""),
+        filters.use_keywords,
+        filters.fuzzy.unwrap_or(false),
+    )?;
+    let query = search.get_text();
     let mut sql_query = format!(
         r#"
       SELECT *
@@ -561,27 +1217,39 @@ fn search_series(filters: Filters) -> Result<Vec<Channel>> {
       AND source_id = ?
       AND series_id = ?
       "#,
-        get_keywords_sql(keywords.len()),
+        search.to_sql(SearchTable::Seasons),
     );
+    let count_query = sql_query.clone();
     let order = match filters.sort {
         sort_type::ALPHABETICAL_DESC => "DESC",
         _ => "ASC",
     };
-    sql_query += &format!("\nORDER BY season_number {}", order);
+    if filters.sort == sort_type::RELEVANCE {
+        sql_query += RELEVANCE_ORDER_SQL;
+    } else {
+        sql_query += &format!("\nORDER BY season_number {}", order);
+    }
     sql_query += "\nLIMIT ?, ?";
-    let mut params: Vec<&dyn rusqlite::ToSql> =
-        Vec::with_capacity(2 + filters.source_ids.len() + keywords.len());
-    params.extend(to_to_sql(&keywords));
+    let mut params: Vec<&dyn rusqlite::ToSql> = Vec::with_capacity(2 + filters.source_ids.len());
+    params.extend(search.params(SearchTable::Seasons));
     params.push(filters.source_ids.first().context("no source ids")?);
     params.push(filters.series_id.as_ref().context("no series id")?);
+    let total = get_total(&sql, &count_query, &params, with_total)?;
+    if filters.sort == sort_type::RELEVANCE {
+        params.push(&query);
+    }
     params.push(&offset);
-    params.push(&PAGE_SIZE);
+    params.push(&page_size);
     let channels: Vec<Channel> = sql
         .prepare(&sql_query)?
         .query_map(params_from_iter(params), season_row_to_channel)?
         .filter_map(Result::ok)
         .collect();
-    Ok(channels)
+    Ok(SearchPage {
+        channels,
+        total,
+        next_cursor: None,
+    })
 }
 
 fn season_row_to_channel(row: &Row) -> std::result::Result<Channel, rusqlite::Error> {
@@ -601,6 +1269,9 @@ fn season_row_to_channel(row: &Row) -> std::result::Result<Channel, rusqlite::Er
         url: None,
         episode_num: None,
         hidden: Some(false),
+        original_name: None,
+        tvg_id: None,
+        progress: None,
     })
 }
 
@@ -612,45 +1283,120 @@ fn get_action_params(action: u8) -> Result<(&'static str, u8)> {
         bulk_action_type::UNHIDE => Ok((bulk_action_type::FIELD_HIDDEN, 0)),
         bulk_action_type::FAVORITE => Ok((bulk_action_type::FIELD_FAVORITE, 1)),
         bulk_action_type::UNFAVORITE => Ok((bulk_action_type::FIELD_FAVORITE, 0)),
+        bulk_action_type::HIDE_DEAD => Ok((bulk_action_type::FIELD_HIDDEN, 1)),
         _ => Err(anyhow!("Invalid action")),
     }
 }
 
-pub fn bulk_update(filters: Filters, action: u8) -> Result<()> {
+pub fn bulk_update(filters: Filters, action: u8, list_id: Option<i64>) -> Result<()> {
     if filters.series_id.is_some() && filters.season.is_none() {
         return Ok(());
     }
 
+    if filters.view_type == view_type::SAVED_SEARCHES {
+        return match filters.saved_search_id {
+            Some(id) => bulk_update(get_saved_search_filters(id, &filters)?, action, list_id),
+            None => Ok(()),
+        };
+    }
+
+    let search = query::parse(
+        filters.query.as_deref().unwrap_or(""),
+        filters.use_keywords,
+        filters.fuzzy.unwrap_or(false),
+    )?;
+
+    if action == bulk_action_type::ADD_TO_LIST || action == bulk_action_type::REMOVE_FROM_LIST {
+        if filters.view_type == view_type::HIDDEN
+            || (filters.view_type == view_type::CATEGORIES && filters.group_id.is_none())
+        {
+            return Ok(());
+        }
+        let target = list_id.or(filters.list_id).context("no list id")?;
+        return apply_bulk_list(&filters, action, target, &search);
+    }
+
     let (field, value) = get_action_params(action)?;
 
-    let query = filters.query.as_deref().unwrap_or("");
-    let keywords: Vec<String> = match filters.use_keywords {
-        true => query
-            .split(" ")
-            .map(|f| format!("%{f}%").to_string())
-            .collect(),
-        false => vec![format!("%{query}%")],
-    };
+    if action == bulk_action_type::HIDE_DEAD {
+        if filters.view_type == view_type::HIDDEN {
+            return Ok(());
+        }
+        return apply_bulk_channels(
+            &filters,
+            &get_bulk_update_sql(field, value),
+            Vec::new(),
+            &search,
+            true,
+        );
+    }
 
     if filters.view_type == view_type::CATEGORIES
         && filters.group_id.is_none()
         && filters.series_id.is_none()
     {
-        return apply_bulk_categories(&filters, field, value, &keywords);
+        return apply_bulk_categories(&filters, field, value, &search);
     }
 
     if filters.view_type == view_type::HIDDEN {
-        return apply_bulk_hidden(&filters, field, value, &keywords);
+        return apply_bulk_hidden(&filters, field, value, &search);
     }
 
-    apply_bulk_channels(&filters, field, value, &keywords)
+    apply_bulk_channels(
+        &filters,
+        &get_bulk_update_sql(field, value),
+        Vec::new(),
+        &search,
+        false,
+    )
+}
+
+fn get_bulk_update_sql(field: &str, value: u8) -> String {
+    format!("UPDATE channels SET {field} = {value} WHERE {BULK_CONDITIONS}")
+}
+
+fn apply_bulk_list(
+    filters: &Filters,
+    action: u8,
+    list_id: i64,
+    search: &SearchQuery,
+) -> Result<()> {
+    if action == bulk_action_type::REMOVE_FROM_LIST {
+        let statement = format!(
+            r#"
+            DELETE FROM list_entries
+            WHERE list_id = ?
+            AND EXISTS (
+                SELECT 1 FROM channels
+                WHERE channels.source_id = list_entries.source_id
+                AND COALESCE(channels.original_name, channels.name) = list_entries.channel_name
+                AND {BULK_CONDITIONS}
+            )"#
+        );
+        return apply_bulk_channels(filters, &statement, vec![&list_id], search, false);
+    }
+    let position = get_next_list_position(&*get_conn()?, list_id)?;
+    let statement = format!(
+        r#"
+        INSERT OR IGNORE INTO list_entries (list_id, source_id, channel_name, position)
+        SELECT ?, source_id, COALESCE(original_name, name), ? + ROW_NUMBER() OVER (ORDER BY name)
+        FROM channels
+        WHERE {BULK_CONDITIONS}"#
+    );
+    apply_bulk_channels(
+        filters,
+        &statement,
+        vec![&list_id, &position],
+        search,
+        false,
+    )
 }
 
 fn apply_bulk_categories(
     filters: &Filters,
     field: &str,
     value: u8,
-    keywords: &[String],
+    search: &SearchQuery,
 ) -> Result<()> {
     if field == bulk_action_type::FIELD_FAVORITE {
         return Ok(());
@@ -672,7 +1418,7 @@ fn apply_bulk_categories(
         "#,
         field,
         value,
-        get_keywords_sql(keywords.len()),
+        search.to_sql(SearchTable::Groups),
         generate_placeholders(filters.source_ids.len()),
         generate_placeholders(media_types.len())
     );
@@ -680,8 +1426,8 @@ fn apply_bulk_categories(
     sql_query += "\nAND hidden = 0";
 
     let mut params: Vec<&dyn rusqlite::ToSql> =
-        Vec::with_capacity(keywords.len() + filters.source_ids.len() + media_types.len());
-    params.extend(to_to_sql(keywords));
+        Vec::with_capacity(filters.source_ids.len() + media_types.len());
+    params.extend(search.params(SearchTable::Groups));
     params.extend(to_to_sql(&filters.source_ids));
     params.extend(to_to_sql(media_types));
 
@@ -689,7 +1435,12 @@ fn apply_bulk_categories(
     Ok(())
 }
 
-fn apply_bulk_hidden(filters: &Filters, field: &str, value: u8, keywords: &[String]) -> Result<()> {
+fn apply_bulk_hidden(
+    filters: &Filters,
+    field: &str,
+    value: u8,
+    search: &SearchQuery,
+) -> Result<()> {
     let sql = get_conn()?;
     let media_types = match filters.series_id.is_some() {
         true => vec![1],
@@ -713,11 +1464,11 @@ fn apply_bulk_hidden(filters: &Filters, field: &str, value: u8, keywords: &[Stri
         "#,
         field,
         value,
-        get_keywords_sql(keywords.len()),
+        search.to_sql(SearchTable::Channels),
         generate_placeholders(media_types.len()),
         generate_placeholders(filters.source_ids.len())
     );
-    params.extend(to_to_sql(keywords));
+    params.extend(search.params(SearchTable::Channels));
     params.extend(to_to_sql(&media_types));
     params.extend(to_to_sql(&filters.source_ids));
 
@@ -736,11 +1487,11 @@ fn apply_bulk_hidden(filters: &Filters, field: &str, value: u8, keywords: &[Stri
             "#,
             field,
             value,
-            get_keywords_sql(keywords.len()),
+            search.to_sql(SearchTable::Groups),
             generate_placeholders(filters.source_ids.len()),
             generate_placeholders(media_types.len())
         );
-        params_groups.extend(to_to_sql(keywords));
+        params_groups.extend(search.params(SearchTable::Groups));
         params_groups.extend(to_to_sql(&filters.source_ids));
         params_groups.extend(to_to_sql(&media_types));
         sql.execute(&sql_query_groups, params_from_iter(params_groups))?;
@@ -750,9 +1501,10 @@ fn apply_bulk_hidden(filters: &Filters, field: &str, value: u8, keywords: &[Stri
 
 fn apply_bulk_channels(
     filters: &Filters,
-    field: &str,
-    value: u8,
-    keywords: &[String],
+    statement: &str,
+    statement_params: Vec<&dyn rusqlite::ToSql>,
+    search: &SearchQuery,
+    dead_only: bool,
 ) -> Result<()> {
     let sql = get_conn()?;
     let media_types = match filters.series_id.is_some() {
@@ -763,18 +1515,15 @@ fn apply_bulk_channels(
             .context("media types not found")?,
     };
 
+    let list_id = get_list_filter(filters);
     let mut sql_query = format!(
         r#"
-        UPDATE channels
-        SET {} = {}
-        WHERE ({})
+        ({})
         AND media_type IN ({})
         AND source_id IN ({})
         AND url IS NOT NULL
         AND hidden = 0"#,
-        field,
-        value,
-        get_keywords_sql(keywords.len()),
+        search.to_sql(SearchTable::Channels),
         generate_placeholders(media_types.len()),
         generate_placeholders(filters.source_ids.len()),
     );
@@ -783,6 +1532,12 @@ fn apply_bulk_channels(
         sql_query += "\nAND favorite = 1";
     }
 
+    if dead_only {
+        sql_query += &format!("\nAND {DEAD_CHANNEL_SQL}");
+    } else if filters.exclude_dead.unwrap_or(false) {
+        sql_query += &format!("\nAND NOT {DEAD_CHANNEL_SQL}");
+    }
+
     if filters.series_id.is_some() {
         sql_query += "\nAND series_id = ?";
     } else if filters.group_id.is_some() {
@@ -796,9 +1551,12 @@ fn apply_bulk_channels(
         sql_query += "\nAND last_watched IS NOT NULL";
     }
 
-    let mut params: Vec<&dyn rusqlite::ToSql> =
-        Vec::with_capacity(media_types.len() + filters.source_ids.len() + keywords.len() + 3);
-    params.extend(to_to_sql(keywords));
+    if list_id.is_some() {
+        sql_query += &format!("\nAND {LIST_MEMBER_SQL}");
+    }
+
+    let mut params = statement_params;
+    params.extend(search.params(SearchTable::Channels));
     params.extend(to_to_sql(&media_types));
     params.extend(to_to_sql(&filters.source_ids));
     if let Some(ref series_id) = filters.series_id {
@@ -809,73 +1567,89 @@ fn apply_bulk_channels(
     if let Some(ref season) = filters.season {
         params.push(season);
     }
+    if let Some(ref list_id) = list_id {
+        params.push(list_id);
+    }
 
-    sql.execute(&sql_query, params_from_iter(params))?;
+    sql.execute(
+        &statement.replace(BULK_CONDITIONS, &sql_query),
+        params_from_iter(params),
+    )?;
     Ok(())
 }
 
-fn search_hidden(filters: Filters) -> Result<Vec<Channel>> {
+fn search_hidden(filters: Filters, with_total: bool) -> Result<SearchPage> {
     let sql = get_conn()?;
-    let offset: u16 = filters.page as u16 * PAGE_SIZE as u16 - PAGE_SIZE as u16;
+    let page_size = get_page_size(&filters);
+    let offset = get_offset(&filters, page_size);
 
     let media_types = match filters.series_id.is_some() {
         true => vec![1],
         false => filters.media_types.clone().unwrap(),
     };
 
-    let query = filters.query.unwrap_or("".to_string());
-    let keywords: Vec<String> = match filters.use_keywords {
-        true => query
-            .split(" ")
-            .map(|f| format!("%{f}%").to_string())
-            .collect(),
-        false => vec![format!("%{query}%")],
-    };
+    let search = query::parse(
+        filters.query.as_deref().unwrap_or(""),
+        filters.use_keywords,
+        filters.fuzzy.unwrap_or(false),
+    )?;
+    let query = search.get_text();
 
-    let keywords_sql = get_keywords_sql(keywords.len());
+    let channels_keywords_sql = search.to_sql(SearchTable::Channels);
+    let groups_keywords_sql = search.to_sql(SearchTable::Groups);
     let media_placeholders = generate_placeholders(media_types.len());
     let source_placeholders = generate_placeholders(filters.source_ids.len());
 
-    let sql_query = format!(
+    let order_sql = match filters.sort {
+        sort_type::RELEVANCE => RELEVANCE_ORDER_SQL,
+        _ => "\nORDER BY name ASC",
+    };
+
+    let union_query = format!(
         r#"
-        SELECT id, image, name, series_id, source_id, stream_id, tv_archive, url, episode_num, hidden, media_type, NULL as group_id, NULL as season_id, favorite
+        SELECT id, image, name, series_id, source_id, stream_id, tv_archive, url, episode_num, hidden, media_type, NULL as group_id, NULL as season_id, favorite, original_name, tvg_id
         FROM channels
         WHERE ({})
         AND media_type IN ({})
         AND source_id IN ({})
-        AND hidden = 1
+        AND hidden = 1{}
         UNION ALL
-        SELECT id, image, name, NULL as series_id, source_id, NULL as stream_id, NULL as tv_archive, NULL as url, NULL as episode_num, hidden, 3 as media_type, NULL as group_id, NULL as season_id, 0 as favorite
+        SELECT id, image, name, NULL as series_id, source_id, NULL as stream_id, NULL as tv_archive, NULL as url, NULL as episode_num, hidden, 3 as media_type, NULL as group_id, NULL as season_id, 0 as favorite, NULL as original_name, NULL as tvg_id
         FROM groups
         WHERE ({})
         AND source_id IN ({})
         AND (media_type IS NULL OR media_type IN ({}))
-        AND hidden = 1
-        ORDER BY name ASC
-        LIMIT ?, ?
+        AND hidden = 1{}
         "#,
-        keywords_sql,
+        channels_keywords_sql,
         media_placeholders,
         source_placeholders,
-        keywords_sql,
+        get_lock_sql(CHANNEL_LOCK_SQL)?,
+        groups_keywords_sql,
         source_placeholders,
-        media_placeholders
+        media_placeholders,
+        get_lock_sql(GROUP_LOCK_SQL)?,
     );
+    let sql_query = format!("SELECT * FROM ({union_query}){order_sql}\nLIMIT ?, ?");
 
     let mut params: Vec<&dyn rusqlite::ToSql> = Vec::new();
 
     // Channels params
-    params.extend(to_to_sql(&keywords));
+    params.extend(search.params(SearchTable::Channels));
     params.extend(to_to_sql(&media_types));
     params.extend(to_to_sql(&filters.source_ids));
 
     // Groups params
-    params.extend(to_to_sql(&keywords));
+    params.extend(search.params(SearchTable::Groups));
     params.extend(to_to_sql(&filters.source_ids));
     params.extend(to_to_sql(&media_types));
+    let total = get_total(&sql, &union_query, &params, with_total)?;
 
+    if filters.sort == sort_type::RELEVANCE {
+        params.push(&query);
+    }
     params.push(&offset);
-    params.push(&PAGE_SIZE);
+    params.push(&page_size);
 
     let channels: Vec<Channel> = sql
         .prepare(&sql_query)?
@@ -883,20 +1657,17 @@ fn search_hidden(filters: Filters) -> Result<Vec<Channel>> {
         .filter_map(Result::ok)
         .collect();
 
-    Ok(channels)
+    Ok(SearchPage {
+        channels,
+        total,
+        next_cursor: None,
+    })
 }
 
 fn to_to_sql<T: rusqlite::ToSql>(values: &[T]) -> Vec<&dyn rusqlite::ToSql> {
     values.iter().map(|x| x as &dyn rusqlite::ToSql).collect()
 }
 
-fn get_keywords_sql(size: usize) -> String {
-    std::iter::repeat("name LIKE ?")
-        .take(size)
-        .collect::<Vec<_>>()
-        .join(" AND ")
-}
-
 fn generate_placeholders(size: usize) -> String {
     std::iter::repeat("?")
         .take(size)
@@ -926,18 +1697,17 @@ fn to_sql_like(query: Option<String>) -> String {
     query.map(|x| format!("%{x}%")).unwrap_or("%".to_string())
 }
 
-pub fn search_group(filters: Filters) -> Result<Vec<Channel>> {
+fn search_group(filters: Filters, with_total: bool) -> Result<SearchPage> {
     let sql = get_conn()?;
-    let offset: u16 = filters.page as u16 * PAGE_SIZE as u16 - PAGE_SIZE as u16;
-    let query = filters.query.unwrap_or("".to_string());
+    let page_size = get_page_size(&filters);
+    let offset = get_offset(&filters, page_size);
     let media_types = filters.media_types.context("no media types")?;
-    let keywords: Vec<String> = match filters.use_keywords {
-        true => query
-            .split(" ")
-            .map(|f| format!("%{f}%").to_string())
-            .collect(),
-        false => vec![format!("%{query}%")],
-    };
+    let search = query::parse(
+        filters.query.as_deref().unwrap_or(""),
+        filters.use_keywords,
+        filters.fuzzy.unwrap_or(false),
+    )?;
+    let query = search.get_text();
     let mut params: Vec<&dyn rusqlite::ToSql> = Vec::with_capacity(2 + filters.source_ids.len());
     let mut sql_query = format!(
         r#"
@@ -947,12 +1717,18 @@ pub fn search_group(filters: Filters) -> Result<Vec<Channel>> {
         AND source_id in ({})
         AND (media_type IS NULL OR media_type in ({}))
     "#,
-        get_keywords_sql(keywords.len()),
+        search.to_sql(SearchTable::Groups),
         generate_placeholders(filters.source_ids.len()),
         generate_placeholders(media_types.len())
     );
     sql_query += "\nAND hidden = 0";
-    if filters.sort != sort_type::PROVIDER {
+    sql_query += get_lock_sql(GROUP_LOCK_SQL)?;
+    let count_query = sql_query.clone();
+    if filters.sort == sort_type::RELEVANCE {
+        sql_query += RELEVANCE_ORDER_SQL;
+    } else if filters.sort == sort_type::CUSTOM {
+        sql_query += CUSTOM_ORDER_SQL;
+    } else if filters.sort != sort_type::PROVIDER {
         let order = match filters.sort {
             sort_type::ALPHABETICAL_ASC => "ASC",
             sort_type::ALPHABETICAL_DESC => "DESC",
@@ -961,17 +1737,704 @@ pub fn search_group(filters: Filters) -> Result<Vec<Channel>> {
         sql_query += &format!("\nORDER BY name {}", order);
     }
     sql_query += "\nLIMIT ?, ?";
-    params.extend(to_to_sql(&keywords));
+    params.extend(search.params(SearchTable::Groups));
     params.extend(to_to_sql(&filters.source_ids));
     params.extend(to_to_sql(&media_types));
+    let total = get_total(&sql, &count_query, &params, with_total)?;
+    if filters.sort == sort_type::RELEVANCE {
+        params.push(&query);
+    }
     params.push(&offset);
-    params.push(&PAGE_SIZE);
+    params.push(&page_size);
     let channels: Vec<Channel> = sql
         .prepare(&sql_query)?
         .query_map(params_from_iter(params), row_to_group)?
         .filter_map(Result::ok)
         .collect();
-    Ok(channels)
+    Ok(SearchPage {
+        channels,
+        total,
+        next_cursor: None,
+    })
+}
+
+fn search_named(
+    table: &str,
+    media_type: u8,
+    condition: &str,
+    filters: Filters,
+    with_total: bool,
+) -> Result<SearchPage> {
+    let sql = get_conn()?;
+    let page_size = get_page_size(&filters);
+    let offset = get_offset(&filters, page_size);
+    let query = to_sql_like(filters.query);
+    let count_query = format!("SELECT id FROM {table} WHERE name LIKE ? {condition}");
+    let total = get_total(&sql, &count_query, &[&query], with_total)?;
+    let channels: Vec<Channel> = sql
+        .prepare(&format!(
+            r#"
+            SELECT id, name
+            FROM {table}
+            WHERE name LIKE ? {condition}
+            ORDER BY name ASC
+            LIMIT ?, ?
+        "#
+        ))?
+        .query_map(params![query, offset, page_size], |row| {
+            let mut channel = row_to_saved_search_channel(row)?;
+            channel.media_type = media_type;
+            Ok(channel)
+        })?
+        .filter_map(Result::ok)
+        .collect();
+    Ok(SearchPage {
+        channels,
+        total,
+        next_cursor: None,
+    })
+}
+
+fn row_to_saved_search_channel(row: &Row) -> std::result::Result<Channel, rusqlite::Error> {
+    Ok(Channel {
+        id: row.get("id")?,
+        name: row.get("name")?,
+        group: None,
+        image: None,
+        media_type: media_type::SAVED_SEARCH,
+        url: None,
+        series_id: None,
+        group_id: None,
+        favorite: false,
+        source_id: None,
+        stream_id: None,
+        tv_archive: None,
+        season_id: None,
+        episode_num: None,
+        hidden: Some(false),
+        original_name: None,
+        tvg_id: None,
+        progress: None,
+    })
+}
+
+pub fn get_active_profile() -> Result<i64> {
+    get_active_profile_from(&*get_conn()?)
+}
+
+fn get_active_profile_from(sql: &rusqlite::Connection) -> Result<i64> {
+    let id: Option<String> = sql
+        .query_row(
+            "SELECT value FROM Settings WHERE key = ?",
+            params![settings::ACTIVE_PROFILE],
+            |r| r.get(0),
+        )
+        .optional()?;
+    Ok(id.and_then(|id| id.parse().ok()).unwrap_or(DEFAULT_PROFILE))
+}
+
+pub fn get_profiles() -> Result<Vec<Profile>> {
+    let sql = get_conn()?;
+    let profiles = sql
+        .prepare("SELECT * FROM profiles ORDER BY id ASC")?
+        .query_map([], |row| {
+            Ok(Profile {
+                id: row.get("id")?,
+                name: row.get("name")?,
+                volume: row.get("volume")?,
+                default_view: row.get("default_view")?,
+                audio_languages: row.get("audio_languages")?,
+                subtitle_languages: row.get("subtitle_languages")?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(profiles)
+}
+
+pub fn save_profile(profile: Profile) -> Result<()> {
+    let sql = get_conn()?;
+    match profile.id {
+        Some(id) => sql.execute(
+            "UPDATE profiles SET name = ? WHERE id = ?",
+            params![profile.name, id],
+        )?,
+        None => sql.execute(
+            "INSERT INTO profiles (name) VALUES (?)",
+            params![profile.name],
+        )?,
+    };
+    Ok(())
+}
+
+pub fn delete_profile(id: i64) -> Result<()> {
+    if id == DEFAULT_PROFILE {
+        bail!("The default profile can't be deleted");
+    }
+    if id == get_active_profile()? {
+        bail!("The active profile can't be deleted, switch to another profile first");
+    }
+    do_tx(|tx| {
+        tx.execute(
+            "DELETE FROM list_entries WHERE list_id IN (SELECT id FROM lists WHERE profile_id = ?)",
+            params![id],
+        )?;
+        tx.execute("DELETE FROM lists WHERE profile_id = ?", params![id])?;
+        tx.execute(
+            "DELETE FROM profile_state WHERE profile_id = ?",
+            params![id],
+        )?;
+        tx.execute(
+            "DELETE FROM watch_progress WHERE profile_id = ?",
+            params![id],
+        )?;
+        tx.execute("DELETE FROM profiles WHERE id = ?", params![id])?;
+        Ok(())
+    })
+}
+
+pub fn switch_profile(id: i64) -> Result<()> {
+    let current = get_active_profile()?;
+    if current == id {
+        return Ok(());
+    }
+    let settings = settings::get_settings()?;
+    let target = get_profiles()?
+        .into_iter()
+        .find(|p| p.id == Some(id))
+        .context("Profile not found")?;
+    do_tx(|tx| {
+        tx.execute(
+            r#"
+            UPDATE profiles
+            SET volume = ?, default_view = ?, audio_languages = ?, subtitle_languages = ?
+            WHERE id = ?
+            "#,
+            params![
+                settings.volume,
+                settings.default_view,
+                settings.audio_languages,
+                settings.subtitle_languages,
+                current
+            ],
+        )?;
+        save_profile_state(tx, current)?;
+        load_profile_state(tx, id)?;
+        Ok(())
+    })?;
+    let mut map: HashMap<String, Option<String>> = HashMap::new();
+    map.insert(settings::ACTIVE_PROFILE.to_string(), Some(id.to_string()));
+    if let Some(volume) = target.volume {
+        map.insert(settings::VOLUME.to_string(), Some(volume.to_string()));
+    }
+    if let Some(default_view) = target.default_view {
+        map.insert(
+            settings::DEFAULT_VIEW.to_string(),
+            Some(default_view.to_string()),
+        );
+    }
+    if let Some(audio_languages) = target.audio_languages {
+        map.insert(settings::AUDIO_LANGUAGES.to_string(), Some(audio_languages));
+    }
+    if let Some(subtitle_languages) = target.subtitle_languages {
+        map.insert(
+            settings::SUBTITLE_LANGUAGES.to_string(),
+            Some(subtitle_languages),
+        );
+    }
+    update_settings(map)
+}
+
+fn save_profile_state(tx: &Transaction, profile_id: i64) -> Result<()> {
+    tx.execute(
+        "DELETE FROM profile_state WHERE profile_id = ?",
+        params![profile_id],
+    )?;
+    tx.execute(
+        r#"
+        INSERT INTO profile_state (profile_id, source_id, name, is_group, favorite, last_watched, hidden)
+        SELECT ?, source_id, COALESCE(original_name, name), 0, favorite, last_watched, hidden
+        FROM channels
+        WHERE (favorite = 1 OR last_watched IS NOT NULL OR hidden = 1)
+        AND series_id IS NULL
+    "#,
+        params![profile_id],
+    )?;
+    tx.execute(
+        r#"
+        INSERT INTO profile_state (profile_id, source_id, name, is_group, hidden)
+        SELECT ?, source_id, name, 1, hidden
+        FROM groups
+        WHERE hidden = 1
+    "#,
+        params![profile_id],
+    )?;
+    Ok(())
+}
+
+fn load_profile_state(tx: &Transaction, profile_id: i64) -> Result<()> {
+    tx.execute(
+        r#"
+        UPDATE channels
+        SET favorite = 0, last_watched = NULL, hidden = 0
+        WHERE favorite = 1 OR last_watched IS NOT NULL OR hidden = 1
+    "#,
+        [],
+    )?;
+    tx.execute("UPDATE groups SET hidden = 0 WHERE hidden = 1", [])?;
+    tx.execute(
+        r#"
+        UPDATE channels
+        SET favorite = s.favorite, last_watched = s.last_watched, hidden = s.hidden
+        FROM profile_state s
+        WHERE s.profile_id = ?
+        AND s.is_group = 0
+        AND s.source_id = channels.source_id
+        AND s.name = COALESCE(channels.original_name, channels.name)
+    "#,
+        params![profile_id],
+    )?;
+    tx.execute(
+        r#"
+        UPDATE groups
+        SET hidden = s.hidden
+        FROM profile_state s
+        WHERE s.profile_id = ?
+        AND s.is_group = 1
+        AND s.source_id = groups.source_id
+        AND s.name = groups.name
+    "#,
+        params![profile_id],
+    )?;
+    Ok(())
+}
+
+pub fn save_watch_progress(channel_id: i64, position: f64, duration: f64) -> Result<()> {
+    if duration <= 0.0 {
+        return Ok(());
+    }
+    let sql = get_conn()?;
+    let profile_id = get_active_profile()?;
+    if position < MIN_PROGRESS_SECONDS || position / duration >= COMPLETED_RATIO {
+        sql.execute(
+            r#"
+            DELETE FROM watch_progress
+            WHERE profile_id = ?
+            AND EXISTS (
+                SELECT 1 FROM channels
+                WHERE channels.id = ?
+                AND channels.source_id = watch_progress.source_id
+                AND COALESCE(channels.original_name, channels.name) = watch_progress.channel_name
+            )
+        "#,
+            params![profile_id, channel_id],
+        )?;
+        return Ok(());
+    }
+    sql.execute(
+        r#"
+        INSERT INTO watch_progress (profile_id, source_id, channel_name, position, duration, updated_at)
+        SELECT ?, source_id, COALESCE(original_name, name), ?, ?, strftime('%s', 'now')
+        FROM channels
+        WHERE id = ?
+        ON CONFLICT (profile_id, source_id, channel_name) DO UPDATE
+        SET position = excluded.position,
+            duration = excluded.duration,
+            updated_at = excluded.updated_at
+    "#,
+        params![profile_id, position, duration, channel_id],
+    )?;
+    Ok(())
+}
+
+pub fn get_watch_progress(channel_id: i64) -> Result<Option<f64>> {
+    let sql = get_conn()?;
+    let position = sql
+        .query_row(
+            r#"
+            SELECT p.position
+            FROM watch_progress p
+            JOIN channels c ON c.source_id = p.source_id
+            AND COALESCE(c.original_name, c.name) = p.channel_name
+            WHERE c.id = ?
+            AND p.profile_id = ?
+        "#,
+            params![channel_id, get_active_profile()?],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(position)
+}
+
+fn search_continue_watching(filters: Filters, with_total: bool) -> Result<SearchPage> {
+    let sql = get_conn()?;
+    let page_size = get_page_size(&filters);
+    let offset = get_offset(&filters, page_size);
+    let profile_id = get_active_profile()?;
+    let search = query::parse(
+        filters.query.as_deref().unwrap_or(""),
+        filters.use_keywords,
+        filters.fuzzy.unwrap_or(false),
+    )?;
+    let mut sql_query = format!(
+        r#"
+        SELECT * FROM (
+            SELECT channels.*,
+            100.0 * p.position / p.duration AS progress,
+            p.updated_at AS progress_updated_at
+            FROM channels
+            JOIN watch_progress p ON p.source_id = channels.source_id
+            AND p.channel_name = COALESCE(channels.original_name, channels.name)
+            WHERE p.profile_id = ?
+        )
+        WHERE ({})
+        AND source_id IN ({})
+        AND url IS NOT NULL
+        AND hidden = 0"#,
+        search.to_sql(SearchTable::Channels),
+        generate_placeholders(filters.source_ids.len()),
+    );
+    sql_query += get_lock_sql(CHANNEL_LOCK_SQL)?;
+    let mut params: Vec<&dyn rusqlite::ToSql> = vec![&profile_id];
+    params.extend(search.params(SearchTable::Channels));
+    params.extend(to_to_sql(&filters.source_ids));
+    let total = get_total(&sql, &sql_query, &params, with_total)?;
+    sql_query += "\nORDER BY progress_updated_at DESC\nLIMIT ?, ?";
+    params.push(&offset);
+    params.push(&page_size);
+    let channels: Vec<Channel> = sql
+        .prepare(&sql_query)?
+        .query_map(params_from_iter(params), |row| {
+            let mut channel = row_to_channel(row)?;
+            channel.progress = row.get("progress")?;
+            Ok(channel)
+        })?
+        .filter_map(Result::ok)
+        .collect();
+    Ok(SearchPage {
+        channels,
+        total,
+        next_cursor: None,
+    })
+}
+
+fn get_list_filter(filters: &Filters) -> Option<i64> {
+    filters
+        .list_id
+        .filter(|_| filters.view_type == view_type::LIST && filters.series_id.is_none())
+}
+
+pub fn get_lists() -> Result<Vec<UserList>> {
+    let sql = get_conn()?;
+    let lists = sql
+        .prepare("SELECT id, name FROM lists WHERE profile_id = ? ORDER BY name ASC")?
+        .query_map(params![get_active_profile()?], |row| {
+            Ok(UserList {
+                id: row.get("id")?,
+                name: row.get("name")?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(lists)
+}
+
+fn check_list_owner(sql: &rusqlite::Connection, list_id: i64) -> Result<()> {
+    let owned = sql
+        .query_row(
+            "SELECT 1 FROM lists WHERE id = ? AND profile_id = ?",
+            params![list_id, get_active_profile_from(sql)?],
+            |r| r.get::<_, u8>(0),
+        )
+        .optional()?
+        .is_some();
+    if !owned {
+        bail!("List not found in the active profile");
+    }
+    Ok(())
+}
+
+pub fn save_list(list: UserList) -> Result<()> {
+    let sql = get_conn()?;
+    match list.id {
+        Some(id) => {
+            check_list_owner(&sql, id)?;
+            sql.execute(
+                "UPDATE lists SET name = ? WHERE id = ?",
+                params![list.name, id],
+            )?
+        }
+        None => sql.execute(
+            "INSERT INTO lists (name, profile_id) VALUES (?, ?)",
+            params![list.name, get_active_profile()?],
+        )?,
+    };
+    Ok(())
+}
+
+pub fn delete_list(id: i64) -> Result<()> {
+    do_tx(|tx| {
+        check_list_owner(tx, id)?;
+        tx.execute("DELETE FROM list_entries WHERE list_id = ?", params![id])?;
+        tx.execute("DELETE FROM lists WHERE id = ?", params![id])?;
+        Ok(())
+    })
+}
+
+fn get_next_list_position(sql: &rusqlite::Connection, list_id: i64) -> Result<i64> {
+    Ok(sql.query_row(
+        "SELECT COALESCE(MAX(position), 0) + 1 FROM list_entries WHERE list_id = ?",
+        params![list_id],
+        |row| row.get(0),
+    )?)
+}
+
+pub fn add_to_list(list_id: i64, channel_id: i64) -> Result<()> {
+    let sql = get_conn()?;
+    check_list_owner(&sql, list_id)?;
+    let position = get_next_list_position(&sql, list_id)?;
+    sql.execute(
+        r#"
+        INSERT OR IGNORE INTO list_entries (list_id, source_id, channel_name, position)
+        SELECT ?, source_id, COALESCE(original_name, name), ?
+        FROM channels
+        WHERE id = ?
+    "#,
+        params![list_id, position, channel_id],
+    )?;
+    Ok(())
+}
+
+pub fn remove_from_list(list_id: i64, channel_id: i64) -> Result<()> {
+    let sql = get_conn()?;
+    check_list_owner(&sql, list_id)?;
+    sql.execute(
+        r#"
+        DELETE FROM list_entries
+        WHERE list_id = ?
+        AND EXISTS (
+            SELECT 1 FROM channels
+            WHERE channels.id = ?
+            AND channels.source_id = list_entries.source_id
+            AND COALESCE(channels.original_name, channels.name) = list_entries.channel_name
+        )
+    "#,
+        params![list_id, channel_id],
+    )?;
+    Ok(())
+}
+
+pub fn reorder_list(list_id: i64, channel_ids: Vec<i64>) -> Result<()> {
+    do_tx(|tx| {
+        check_list_owner(tx, list_id)?;
+        for (position, channel_id) in channel_ids.iter().enumerate() {
+            tx.execute(
+                r#"
+                UPDATE list_entries
+                SET position = ?
+                WHERE list_id = ?
+                AND EXISTS (
+                    SELECT 1 FROM channels
+                    WHERE channels.id = ?
+                    AND channels.source_id = list_entries.source_id
+                    AND COALESCE(channels.original_name, channels.name) = list_entries.channel_name
+                )
+            "#,
+                params![position as i64 + 1, list_id, channel_id],
+            )?;
+        }
+        Ok(())
+    })
+}
+
+pub fn move_in_list(list_id: i64, channel_id: i64, position: usize) -> Result<()> {
+    let sql = get_conn()?;
+    let mut ids: Vec<i64> = sql
+        .prepare(
+            r#"
+            SELECT c.id
+            FROM list_entries e
+            JOIN channels c ON c.source_id = e.source_id
+            AND COALESCE(c.original_name, c.name) = e.channel_name
+            WHERE e.list_id = ?
+            ORDER BY e.position ASC, c.name ASC
+        "#,
+        )?
+        .query_map(params![list_id], |row| row.get(0))?
+        .collect::<Result<Vec<i64>, _>>()?;
+    move_id(&mut ids, channel_id, position);
+    reorder_list(list_id, ids)
+}
+
+fn move_id(ids: &mut Vec<i64>, id: i64, position: usize) {
+    ids.retain(|x| *x != id);
+    ids.insert(position.min(ids.len()), id);
+}
+
+pub fn move_channel(id: i64, position: usize) -> Result<()> {
+    let sql = get_conn()?;
+    let mut ids: Vec<i64> = sql
+        .prepare(
+            r#"
+            SELECT id
+            FROM channels
+            WHERE source_id = (SELECT source_id FROM channels WHERE id = ?1)
+            AND group_id IS (SELECT group_id FROM channels WHERE id = ?1)
+            AND series_id IS NULL
+            AND hidden = 0
+            ORDER BY position IS NULL, position ASC, name ASC
+        "#,
+        )?
+        .query_map(params![id], |row| row.get(0))?
+        .collect::<Result<Vec<i64>, _>>()?;
+    move_id(&mut ids, id, position);
+    reorder_channels(ids)
+}
+
+pub fn reorder_channels(channel_ids: Vec<i64>) -> Result<()> {
+    do_tx(|tx| {
+        for (position, id) in channel_ids.iter().enumerate() {
+            tx.execute(
+                "UPDATE channels SET position = ? WHERE id = ?",
+                params![position as i64 + 1, id],
+            )?;
+        }
+        Ok(())
+    })
+}
+
+pub fn move_group(id: i64, position: usize) -> Result<()> {
+    let sql = get_conn()?;
+    let mut ids: Vec<i64> = sql
+        .prepare(
+            r#"
+            SELECT id
+            FROM groups
+            WHERE source_id = (SELECT source_id FROM groups WHERE id = ?)
+            AND hidden = 0
+            ORDER BY position IS NULL, position ASC, name ASC
+        "#,
+        )?
+        .query_map(params![id], |row| row.get(0))?
+        .collect::<Result<Vec<i64>, _>>()?;
+    move_id(&mut ids, id, position);
+    reorder_groups(ids)
+}
+
+pub fn reorder_groups(group_ids: Vec<i64>) -> Result<()> {
+    do_tx(|tx| {
+        for (position, id) in group_ids.iter().enumerate() {
+            tx.execute(
+                "UPDATE groups SET position = ? WHERE id = ?",
+                params![position as i64 + 1, id],
+            )?;
+        }
+        Ok(())
+    })
+}
+
+pub fn get_channel_lists(channel_id: i64) -> Result<Vec<i64>> {
+    let sql = get_conn()?;
+    let lists = sql
+        .prepare(
+            r#"
+            SELECT e.list_id
+            FROM list_entries e
+            JOIN lists l ON l.id = e.list_id
+            JOIN channels c ON c.source_id = e.source_id
+            AND COALESCE(c.original_name, c.name) = e.channel_name
+            WHERE c.id = ?
+            AND l.profile_id = ?
+        "#,
+        )?
+        .query_map(params![channel_id, get_active_profile()?], |row| row.get(0))?
+        .collect::<Result<Vec<i64>, _>>()?;
+    Ok(lists)
+}
+
+fn get_saved_search_filters(id: i64, current: &Filters) -> Result<Filters> {
+    let mut filters = get_saved_search(id)?.filters;
+    if filters.view_type == view_type::SAVED_SEARCHES {
+        bail!("A saved search can't point to another saved search");
+    }
+    filters.page = current.page;
+    filters.page_size = current.page_size;
+    filters.cursor = current.cursor.clone();
+    Ok(filters)
+}
+
+pub fn get_saved_searches() -> Result<Vec<SavedSearch>> {
+    let sql = get_conn()?;
+    let searches = sql
+        .prepare("SELECT * FROM saved_searches ORDER BY name ASC")?
+        .query_map([], row_to_saved_search)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(searches)
+}
+
+pub fn get_saved_search(id: i64) -> Result<SavedSearch> {
+    let sql = get_conn()?;
+    Ok(sql.query_row(
+        "SELECT * FROM saved_searches WHERE id = ?",
+        params![id],
+        row_to_saved_search,
+    )?)
+}
+
+fn row_to_saved_search(row: &Row) -> std::result::Result<SavedSearch, rusqlite::Error> {
+    let filters: String = row.get("filters")?;
+    Ok(SavedSearch {
+        id: row.get("id")?,
+        name: row.get("name")?,
+        filters: serde_json::from_str(&filters).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Text, Box::new(e))
+        })?,
+    })
+}
+
+pub fn save_search(search: SavedSearch) -> Result<()> {
+    let sql = get_conn()?;
+    let filters = serde_json::to_string(&search.filters)?;
+    match search.id {
+        Some(id) => sql.execute(
+            "UPDATE saved_searches SET name = ?, filters = ? WHERE id = ?",
+            params![search.name, filters, id],
+        )?,
+        None => sql.execute(
+            "INSERT INTO saved_searches (name, filters) VALUES (?, ?)",
+            params![search.name, filters],
+        )?,
+    };
+    Ok(())
+}
+
+pub fn delete_saved_search(id: i64) -> Result<()> {
+    let sql = get_conn()?;
+    sql.execute("DELETE FROM saved_searches WHERE id = ?", params![id])?;
+    Ok(())
+}
+
+pub fn saved_search_exists(name: &str) -> Result<bool> {
+    let sql = get_conn()?;
+    Ok(sql
+        .query_row(
+            "SELECT 1 FROM saved_searches WHERE name = ?",
+            params![name],
+            |row| row.get::<_, u8>(0),
+        )
+        .optional()?
+        .is_some())
+}
+
+pub fn get_group_id_by_name(name: &str, source_ids: &[i64]) -> Result<Option<i64>> {
+    let sql = get_conn()?;
+    let query = format!(
+        "SELECT id FROM groups WHERE name = ? AND source_id IN ({}) LIMIT 1",
+        generate_placeholders(source_ids.len())
+    );
+    let mut params: Vec<&dyn rusqlite::ToSql> = vec![&name];
+    params.extend(to_to_sql(source_ids));
+    Ok(sql
+        .query_row(&query, params_from_iter(params), |row| row.get(0))
+        .optional()?)
 }
 
 fn row_to_group(row: &Row) -> std::result::Result<Channel, rusqlite::Error> {
@@ -991,6 +2454,9 @@ fn row_to_group(row: &Row) -> std::result::Result<Channel, rusqlite::Error> {
         season_id: None,
         episode_num: None,
         hidden: row.get("hidden")?,
+        original_name: None,
+        tvg_id: None,
+        progress: None,
     };
     Ok(channel)
 }
@@ -1010,6 +2476,9 @@ fn row_to_channel(row: &Row) -> std::result::Result<Channel, rusqlite::Error> {
         group: None,
         stream_id: row.get("stream_id")?,
         tv_archive: row.get("tv_archive")?,
+        original_name: row.get("original_name")?,
+        tvg_id: row.get("tvg_id")?,
+        progress: None,
         season_id: row.get("season_id")?,
         hidden: row.get("hidden")?,
     };
@@ -1030,44 +2499,93 @@ pub fn delete_channels_by_source(tx: &Transaction, source_id: i64) -> Result<()>
 pub fn delete_seasons_by_source(tx: &Transaction, source_id: i64) -> Result<()> {
     tx.execute(
         r#"
-        DELETE FROM seasons
-        WHERE source_id = ?
+        DELETE FROM seasons
+        WHERE source_id = ?
+    "#,
+        params![source_id],
+    )?;
+    Ok(())
+}
+
+pub fn delete_groups_by_source(tx: &Transaction, source_id: i64) -> Result<()> {
+    tx.execute(
+        r#"
+        DELETE FROM groups
+        WHERE source_id = ?
+    "#,
+        params!(source_id),
+    )?;
+    Ok(())
+}
+
+pub fn delete_source(id: i64) -> Result<()> {
+    let sql = get_conn()?;
+    sql.execute(
+        r#"
+        DELETE FROM channels
+        WHERE source_id = ?;
+    "#,
+        params![id],
+    )?;
+    sql.execute(
+        r#"
+        DELETE FROM groups
+        WHERE source_id = ?;
+    "#,
+        params![id],
+    )?;
+    sql.execute(
+        r#"
+        DELETE FROM seasons
+        WHERE source_id = ?;
+    "#,
+        params![id],
+    )?;
+    sql.execute(
+        r#"
+        DELETE FROM name_rules
+        WHERE source_id = ?;
+    "#,
+        params![id],
+    )?;
+    sql.execute(
+        r#"
+        DELETE FROM channel_health
+        WHERE source_id = ?;
+    "#,
+        params![id],
+    )?;
+    sql.execute(
+        r#"
+        DELETE FROM extra_http_headers
+        WHERE source_id = ?;
     "#,
-        params![source_id],
+        params![id],
     )?;
-    Ok(())
-}
-
-pub fn delete_groups_by_source(tx: &Transaction, source_id: i64) -> Result<()> {
-    tx.execute(
+    sql.execute(
         r#"
-        DELETE FROM groups
-        WHERE source_id = ?
+        DELETE FROM channel_drm
+        WHERE source_id = ?;
     "#,
-        params!(source_id),
+        params![id],
     )?;
-    Ok(())
-}
-
-pub fn delete_source(id: i64) -> Result<()> {
-    let sql = get_conn()?;
     sql.execute(
         r#"
-        DELETE FROM channels
+        DELETE FROM list_entries
         WHERE source_id = ?;
     "#,
         params![id],
     )?;
     sql.execute(
         r#"
-        DELETE FROM groups
+        DELETE FROM profile_state
         WHERE source_id = ?;
     "#,
         params![id],
     )?;
     sql.execute(
         r#"
-        DELETE FROM seasons
+        DELETE FROM watch_progress
         WHERE source_id = ?;
     "#,
         params![id],
@@ -1151,6 +2669,57 @@ pub fn hide_group(group_id: i64, hidden: bool) -> Result<()> {
     Ok(())
 }
 
+pub fn favorite_logical_channel(channel_id: i64, favorite: bool) -> Result<()> {
+    let sql = get_conn()?;
+    sql.execute(
+        r#"
+        UPDATE channels
+        SET favorite = ?1
+        WHERE id = ?2
+        OR merge_key = (SELECT merge_key FROM channels WHERE id = ?2)
+    "#,
+        params![favorite, channel_id],
+    )?;
+    Ok(())
+}
+
+pub fn remove_logical_last_watched(channel_id: i64) -> Result<()> {
+    let sql = get_conn()?;
+    sql.execute(
+        r#"
+        UPDATE channels
+        SET last_watched = NULL
+        WHERE id = ?1
+        OR merge_key = (SELECT merge_key FROM channels WHERE id = ?1)
+    "#,
+        params![channel_id],
+    )?;
+    Ok(())
+}
+
+pub fn get_logical_channel_members(channel_id: i64) -> Result<Vec<Channel>> {
+    let sql = get_conn()?;
+    let channels = sql
+        .prepare(&format!(
+            r#"
+            SELECT *
+            FROM channels
+            WHERE merge_key = (SELECT merge_key FROM channels WHERE id = ?1)
+            AND id != ?1
+            AND url IS NOT NULL
+            AND hidden = 0
+            AND source_id IN (SELECT id FROM sources WHERE enabled = 1)
+            {}
+            ORDER BY id
+        "#,
+            get_lock_sql(CHANNEL_LOCK_SQL)?
+        ))?
+        .query_map(params![channel_id], row_to_channel)?
+        .filter_map(Result::ok)
+        .collect();
+    Ok(channels)
+}
+
 pub fn remove_last_watched(channel_id: i64) -> Result<()> {
     let sql = get_conn()?;
     sql.execute(
@@ -1189,7 +2758,7 @@ fn row_to_source(row: &Row) -> std::result::Result<Source, rusqlite::Error> {
         id: row.get("id")?,
         name: row.get("name")?,
         username: row.get("username")?,
-        password: row.get("password")?,
+        password: crypto::decrypt_password(row.get("password")?),
         url: row.get("url")?,
         source_type: row.get("source_type")?,
         url_origin: None,
@@ -1199,6 +2768,9 @@ fn row_to_source(row: &Row) -> std::result::Result<Source, rusqlite::Error> {
         max_streams: row.get("max_streams")?,
         stream_user_agent: row.get("stream_user_agent")?,
         last_updated: row.get("last_updated")?,
+        proxy: row.get("proxy")?,
+        player: row.get("player")?,
+        mirrors: row.get("mirrors")?,
     })
 }
 
@@ -1225,7 +2797,8 @@ pub fn set_source_enabled(value: bool, source_id: i64) -> Result<()> {
     Ok(())
 }
 
-pub fn add_custom_channel(tx: &Transaction, channel: CustomChannel) -> Result<()> {
+pub fn add_custom_channel(tx: &Transaction, mut channel: CustomChannel) -> Result<()> {
+    m3u::split_custom_channel_url(&mut channel);
     insert_channel(tx, channel.data)?;
     if let Some(mut headers) = channel.headers {
         if channel_headers_empty(&headers) {
@@ -1241,7 +2814,9 @@ fn channel_headers_empty(headers: &ChannelHttpHeaders) -> bool {
     return headers.ignore_ssl.is_none()
         && headers.http_origin.is_none()
         && headers.referrer.is_none()
-        && headers.user_agent.is_none();
+        && headers.user_agent.is_none()
+        && headers.cookies.is_none()
+        && headers.extra_headers.as_ref().is_none_or(|h| h.is_empty());
 }
 
 pub fn get_custom_source(name: String) -> Source {
@@ -1259,6 +2834,9 @@ pub fn get_custom_source(name: String) -> Source {
         max_streams: None,
         stream_user_agent: None,
         last_updated: None,
+        proxy: None,
+        player: None,
+        mirrors: None,
     }
 }
 
@@ -1277,7 +2855,8 @@ pub fn edit_custom_channel(channel: CustomChannel) -> Result<()> {
     }
 }
 
-fn edit_custom_channel_tx(channel: CustomChannel, tx: &Transaction) -> Result<()> {
+fn edit_custom_channel_tx(mut channel: CustomChannel, tx: &Transaction) -> Result<()> {
+    m3u::split_custom_channel_url(&mut channel);
     tx.execute(
         r#"
         UPDATE channels
@@ -1297,27 +2876,38 @@ fn edit_custom_channel_tx(channel: CustomChannel, tx: &Transaction) -> Result<()
         headers.channel_id = channel.data.id;
         tx.execute(
             r#"
-            INSERT INTO channel_http_headers (referrer, user_agent, http_origin, ignore_ssl, channel_id)
-            VALUES (?1, ?2, ?3, ?4, ?5)
+            INSERT INTO channel_http_headers (referrer, user_agent, http_origin, ignore_ssl, channel_id, cookies)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
             ON CONFLICT(channel_id) DO UPDATE SET
                 referrer = ?1,
                 user_agent = ?2,
                 http_origin = ?3,
-                ignore_ssl = ?4
+                ignore_ssl = ?4,
+                cookies = ?6
         "#,
             params![
                 headers.referrer,
                 headers.user_agent,
                 headers.http_origin,
                 headers.ignore_ssl,
-                headers.channel_id
+                headers.channel_id,
+                headers.cookies
             ],
         )?;
+        set_channel_extra_headers(
+            tx,
+            channel.data.id.context("no channel id")?,
+            headers.extra_headers.unwrap_or_default(),
+        )?;
     } else {
         tx.execute(
             "DELETE FROM channel_http_headers WHERE channel_id = ?",
             params![channel.data.id],
         )?;
+        tx.execute(
+            "DELETE FROM extra_http_headers WHERE channel_id = ?",
+            params![channel.data.id],
+        )?;
     }
     Ok(())
 }
@@ -1325,6 +2915,11 @@ fn edit_custom_channel_tx(channel: CustomChannel, tx: &Transaction) -> Result<()
 pub fn delete_custom_channel(id: i64) -> Result<()> {
     let sql = get_conn()?;
     sql.execute("DELETE FROM channels WHERE id = ?", params![id])?;
+    sql.execute(
+        "DELETE FROM extra_http_headers WHERE channel_id = ?",
+        params![id],
+    )?;
+    sql.execute("DELETE FROM channel_drm WHERE channel_id = ?", params![id])?;
     Ok(())
 }
 
@@ -1408,7 +3003,7 @@ pub fn edit_custom_group(group: Group) -> Result<()> {
     Ok(())
 }
 
-fn get_group_by_id(id: i64) -> Result<Option<Group>> {
+pub fn get_group_by_id(id: i64) -> Result<Option<Group>> {
     let sql = get_conn()?;
     let group: Option<Group> = sql
         .query_row(
@@ -1484,7 +3079,7 @@ pub fn group_not_empty(id: i64) -> Result<bool> {
 pub fn get_custom_channels(group_id: Option<i64>, source_id: i64) -> Result<Vec<CustomChannel>> {
     let sql = get_conn()?;
     let mut sql_query = r#"
-        SELECT c.name, c.image, c.url, c.media_type, ch.referrer, ch.user_agent, ch.http_origin, ch.ignore_ssl
+        SELECT c.id, c.name, c.image, c.url, c.media_type, ch.referrer, ch.user_agent, ch.http_origin, ch.ignore_ssl, ch.cookies
         FROM channels c
         LEFT JOIN channel_http_headers ch on ch.channel_id = c.id
         WHERE source_id = ?
@@ -1497,11 +3092,17 @@ pub fn get_custom_channels(group_id: Option<i64>, source_id: i64) -> Result<Vec<
     } else {
         sql_query.push_str("\nAND group_id IS NULL");
     }
-    let result = sql
+    let mut result: Vec<CustomChannel> = sql
         .prepare(&sql_query)?
         .query_map(params_from_iter(params), row_to_custom_channel)?
         .filter_map(Result::ok)
         .collect();
+    for channel in result.iter_mut() {
+        if let (Some(headers), Some(id)) = (channel.headers.as_mut(), channel.data.id) {
+            headers.extra_headers = get_channel_extra_headers(&sql, id)?;
+        }
+        channel.data.id = None;
+    }
     Ok(result)
 }
 
@@ -1515,7 +3116,7 @@ fn row_to_custom_channel(row: &Row) -> Result<CustomChannel, rusqlite::Error> {
             favorite: false,
             group_id: None,
             group: None,
-            id: None,
+            id: row.get("id")?,
             series_id: None,
             source_id: None,
             stream_id: None,
@@ -1523,12 +3124,17 @@ fn row_to_custom_channel(row: &Row) -> Result<CustomChannel, rusqlite::Error> {
             season_id: None,
             episode_num: None,
             hidden: Some(false),
+            original_name: None,
+            tvg_id: None,
+            progress: None,
         },
         headers: Some(ChannelHttpHeaders {
             http_origin: row.get("http_origin")?,
             ignore_ssl: row.get("ignore_ssl")?,
             referrer: row.get("referrer")?,
             user_agent: row.get("user_agent")?,
+            cookies: row.get("cookies")?,
+            extra_headers: None,
             channel_id: None,
             id: None,
         }),
@@ -1585,16 +3191,24 @@ pub fn update_source(source: Source) -> Result<()> {
     sql.execute(
         r#"
         UPDATE sources
-        SET username = ?, password = ?, url = ?, use_tvg_id = ?, user_agent = ?, max_streams = ?, stream_user_agent = ?
+        SET username = ?, password = IIF(?, password, ?), url = ?, use_tvg_id = ?, user_agent = ?, max_streams = ?, stream_user_agent = ?, proxy = ?, player = ?, mirrors = ?
         WHERE id = ?"#,
         params![
             source.username,
-            source.password,
+            source.password.as_deref() == Some(crypto::UNCHANGED_PASSWORD),
+            crypto::encrypt_password(
+                source
+                    .password
+                    .filter(|p| p != crypto::UNCHANGED_PASSWORD)
+            )?,
             source.url,
             source.use_tvg_id,
             source.user_agent,
             source.max_streams,
             source.stream_user_agent,
+            source.proxy,
+            source.player,
+            source.mirrors,
             source.id
         ],
     )?;
@@ -1602,6 +3216,11 @@ pub fn update_source(source: Source) -> Result<()> {
 }
 
 pub fn wipe(tx: &Transaction, id: i64) -> Result<()> {
+    tx.execute(
+        "DELETE FROM extra_http_headers WHERE source_id = ? AND channel_id IS NOT NULL",
+        params![id],
+    )?;
+    tx.execute("DELETE FROM channel_drm WHERE source_id = ?", params![id])?;
     delete_seasons_by_source(tx, id)?;
     delete_channels_by_source(tx, id)?;
     delete_groups_by_source(tx, id)?;
@@ -1667,9 +3286,9 @@ pub fn get_preserve(tx: &Transaction, source_id: i64) -> Result<Vec<ChannelPrese
     let mut channels: Vec<ChannelPreserve> = tx
         .prepare(
             r#"
-              SELECT name, favorite, last_watched, hidden
+              SELECT COALESCE(original_name, name) AS name, favorite, last_watched, hidden, position, locked, playback_overrides
               FROM channels
-              WHERE (favorite = 1 OR last_watched IS NOT NULL OR hidden = 1)
+              WHERE (favorite = 1 OR last_watched IS NOT NULL OR hidden = 1 OR position IS NOT NULL OR locked = 1 OR playback_overrides IS NOT NULL)
               AND series_id IS NULL
               AND source_id = ?
             "#,
@@ -1681,9 +3300,9 @@ pub fn get_preserve(tx: &Transaction, source_id: i64) -> Result<Vec<ChannelPrese
     let groups: Vec<ChannelPreserve> = tx
         .prepare(
             r#"
-              SELECT name, hidden
+              SELECT name, hidden, position, locked
               FROM groups
-              WHERE hidden = 1
+              WHERE (hidden = 1 OR position IS NOT NULL OR locked = 1)
               AND source_id = ?
             "#,
         )?
@@ -1691,7 +3310,24 @@ pub fn get_preserve(tx: &Transaction, source_id: i64) -> Result<Vec<ChannelPrese
         .filter_map(Result::ok)
         .collect();
 
+    let profiles: Vec<ChannelPreserve> = tx
+        .prepare(
+            r#"
+              SELECT profile_id, name, is_group, favorite, last_watched, hidden
+              FROM profile_state
+              WHERE source_id = ?
+              AND profile_id != ?
+            "#,
+        )?
+        .query_map(
+            params![source_id, get_active_profile_from(tx)?],
+            row_to_profile_preserve,
+        )?
+        .filter_map(Result::ok)
+        .collect();
+
     channels.extend(groups);
+    channels.extend(profiles);
     Ok(channels)
 }
 
@@ -1702,6 +3338,10 @@ fn row_to_channel_preserve(row: &Row) -> Result<ChannelPreserve, rusqlite::Error
         last_watched: row.get("last_watched")?,
         hidden: row.get("hidden")?,
         is_group: false,
+        position: row.get("position")?,
+        locked: row.get("locked")?,
+        playback_overrides: row.get("playback_overrides")?,
+        profile_id: None,
     })
 }
 
@@ -1712,6 +3352,24 @@ fn row_to_group_preserve(row: &Row) -> Result<ChannelPreserve, rusqlite::Error>
         favorite: false,
         last_watched: None,
         is_group: true,
+        position: row.get("position")?,
+        locked: row.get("locked")?,
+        playback_overrides: None,
+        profile_id: None,
+    })
+}
+
+fn row_to_profile_preserve(row: &Row) -> Result<ChannelPreserve, rusqlite::Error> {
+    Ok(ChannelPreserve {
+        name: row.get("name")?,
+        favorite: row.get("favorite")?,
+        last_watched: row.get("last_watched")?,
+        hidden: row.get("hidden")?,
+        is_group: row.get("is_group")?,
+        position: None,
+        locked: false,
+        playback_overrides: None,
+        profile_id: row.get("profile_id")?,
     })
 }
 
@@ -1720,29 +3378,41 @@ pub fn restore_preserve(
     source_id: i64,
     preserve: Vec<ChannelPreserve>,
 ) -> Result<()> {
+    let active_profile = get_active_profile_from(tx)?;
     for item in preserve {
-        if item.is_group {
+        if let Some(profile_id) = item.profile_id.filter(|id| *id != active_profile) {
+            restore_profile_state(tx, source_id, profile_id, item)?;
+        } else if item.is_group {
             tx.execute(
                 r#"
                   UPDATE groups
-                  SET hidden = ?
+                  SET hidden = ?, position = ?, locked = ?
                   WHERE name = ?
                   AND source_id = ?
                 "#,
-                params![item.hidden, item.name, source_id],
+                params![
+                    item.hidden,
+                    item.position,
+                    item.locked,
+                    item.name,
+                    source_id
+                ],
             )?;
         } else {
             tx.execute(
                 r#"
                   UPDATE channels
-                  SET favorite = ?, last_watched = ?, hidden = ?
-                  WHERE name = ?
+                  SET favorite = ?, last_watched = ?, hidden = ?, position = ?, locked = ?, playback_overrides = ?
+                  WHERE (original_name = ?7 OR (original_name IS NULL AND name = ?7))
                   AND source_id = ?
                 "#,
                 params![
                     item.favorite,
                     item.last_watched,
                     item.hidden,
+                    item.position,
+                    item.locked,
+                    item.playback_overrides,
                     item.name,
                     source_id
                 ],
@@ -1752,11 +3422,49 @@ pub fn restore_preserve(
     Ok(())
 }
 
+/// The other profiles' state is keyed by name like the active one, but lives in profile_state
+fn restore_profile_state(
+    tx: &Transaction,
+    source_id: i64,
+    profile_id: i64,
+    item: ChannelPreserve,
+) -> Result<()> {
+    tx.execute(
+        r#"
+          DELETE FROM profile_state
+          WHERE profile_id = ?
+          AND source_id = ?
+          AND name = ?
+          AND is_group = ?
+        "#,
+        params![profile_id, source_id, item.name, item.is_group],
+    )?;
+    tx.execute(
+        r#"
+          INSERT INTO profile_state (profile_id, source_id, name, is_group, favorite, last_watched, hidden)
+          SELECT ?1, ?2, ?3, ?4, ?5, ?6, ?7
+          WHERE EXISTS (SELECT 1 FROM profiles WHERE id = ?1)
+        "#,
+        params![
+            profile_id,
+            source_id,
+            item.name,
+            item.is_group,
+            item.favorite,
+            item.last_watched,
+            item.hidden.unwrap_or(false)
+        ],
+    )?;
+    Ok(())
+}
+
 pub fn analyze(tx: &Transaction) -> Result<()> {
     tx.execute("ANALYZE;", params![])?;
     Ok(())
 }
 
+/// Only the active profile's history is in channels, the others' is parked in profile_state,
+/// so every profile keeps its own 36 most recent channels
 pub fn add_last_watched(id: i64) -> Result<()> {
     let sql = get_conn()?;
     sql.execute(
@@ -1798,19 +3506,19 @@ pub fn clear_history() -> Result<()> {
     Ok(())
 }
 
-pub fn find_all_episodes_after(channel: &Channel) -> Result<Vec<String>> {
+pub fn find_all_episodes_after(channel: &Channel) -> Result<Vec<(i64, String)>> {
     let sql = get_conn()?;
     Ok(sql
         .prepare(
             r#"
-        SELECT url FROM channels
+        SELECT id, url FROM channels
         WHERE season_id = ?
         AND episode_num > ?
         ORDER BY episode_num
       "#,
         )?
         .query_map(params![channel.season_id, channel.episode_num], |row| {
-            row.get::<_, String>(0)
+            Ok((row.get(0)?, row.get(1)?))
         })?
         .filter_map(Result::ok)
         .collect())
@@ -1824,3 +3532,459 @@ pub fn update_source_last_updated(source_id: i64) -> Result<()> {
     )?;
     Ok(())
 }
+
+pub fn get_name_rules(tx: &Transaction, source_id: i64) -> Result<Vec<NameRule>> {
+    let rules = tx
+        .prepare(
+            r#"
+            SELECT *
+            FROM name_rules
+            WHERE source_id = ?
+            ORDER BY id
+        "#,
+        )?
+        .query_map(params![source_id], row_to_name_rule)?
+        .filter_map(Result::ok)
+        .collect();
+    Ok(rules)
+}
+
+fn row_to_name_rule(row: &Row) -> Result<NameRule, rusqlite::Error> {
+    Ok(NameRule {
+        id: row.get("id")?,
+        source_id: row.get("source_id")?,
+        rule_type: row.get("rule_type")?,
+        pattern: row.get("pattern")?,
+        replacement: row.get("replacement")?,
+    })
+}
+
+pub fn set_name_rules(tx: &Transaction, source_id: i64, rules: Vec<NameRule>) -> Result<()> {
+    let rewriter = NameRewriter::new(rules.clone())?;
+    tx.execute(
+        "DELETE FROM name_rules WHERE source_id = ?",
+        params![source_id],
+    )?;
+    for rule in rules {
+        tx.execute(
+            r#"
+            INSERT INTO name_rules (source_id, rule_type, pattern, replacement)
+            VALUES (?, ?, ?, ?)
+        "#,
+            params![source_id, rule.rule_type, rule.pattern, rule.replacement],
+        )?;
+    }
+    apply_name_rules(tx, source_id, &rewriter)
+}
+
+fn apply_name_rules(tx: &Transaction, source_id: i64, rewriter: &NameRewriter) -> Result<()> {
+    let channels: Vec<(i64, String)> = tx
+        .prepare(
+            r#"
+            SELECT id, COALESCE(original_name, name)
+            FROM channels
+            WHERE source_id = ?
+            AND series_id IS NULL
+        "#,
+        )?
+        .query_map(params![source_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .filter_map(Result::ok)
+        .collect();
+    let mut stmt = tx.prepare(
+        r#"
+        UPDATE OR IGNORE channels
+        SET name = ?, original_name = ?
+        WHERE id = ?
+    "#,
+    )?;
+    for (id, original_name) in channels {
+        let name = rewriter.rewrite(&original_name);
+        let original_name = (name != original_name).then_some(original_name);
+        stmt.execute(params![name, original_name, id])?;
+    }
+    Ok(())
+}
+
+pub fn get_health_check_channels(source_ids: &[i64]) -> Result<Vec<Channel>> {
+    let sql = get_conn()?;
+    let sql_query = format!(
+        r#"
+        SELECT *
+        FROM channels
+        WHERE source_id IN ({})
+        AND media_type IN (?, ?)
+        AND url IS NOT NULL
+        AND hidden = 0
+        ORDER BY id
+    "#,
+        generate_placeholders(source_ids.len())
+    );
+    let mut params: Vec<&dyn rusqlite::ToSql> = to_to_sql(source_ids);
+    params.push(&media_type::LIVESTREAM);
+    params.push(&media_type::MOVIE);
+    let channels = sql
+        .prepare(&sql_query)?
+        .query_map(params_from_iter(params), row_to_channel)?
+        .filter_map(Result::ok)
+        .collect();
+    Ok(channels)
+}
+
+pub fn set_channel_health(source_id: i64, url: &str, ok: bool) -> Result<()> {
+    let sql = get_conn()?;
+    let now = chrono::Utc::now().timestamp();
+    sql.execute(
+        r#"
+        INSERT INTO channel_health (source_id, url, last_checked, last_ok, failures)
+        VALUES (?1, ?2, ?3, CASE WHEN ?4 THEN ?3 END, CASE WHEN ?4 THEN 0 ELSE 1 END)
+        ON CONFLICT (source_id, url)
+        DO UPDATE SET
+          last_checked = excluded.last_checked,
+          last_ok = COALESCE(excluded.last_ok, last_ok),
+          failures = CASE WHEN ?4 THEN 0 ELSE failures + 1 END
+    "#,
+        params![source_id, url, now, ok],
+    )?;
+    Ok(())
+}
+
+pub fn get_source_passwords() -> Result<Vec<(i64, String)>> {
+    let sql = get_conn()?;
+    let passwords = sql
+        .prepare("SELECT id, password FROM sources WHERE password IS NOT NULL")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .filter_map(Result::ok)
+        .collect();
+    Ok(passwords)
+}
+
+pub fn set_source_password(tx: &Transaction, source_id: i64, password: &str) -> Result<()> {
+    tx.execute(
+        "UPDATE sources SET password = ? WHERE id = ?",
+        params![password, source_id],
+    )?;
+    Ok(())
+}
+
+pub fn replace_url_password(
+    tx: &Transaction,
+    source_id: i64,
+    username: &str,
+    password: &str,
+) -> Result<()> {
+    let from = format!("/{username}/{password}/");
+    let to = format!("/{username}/{}/", xtream::PASSWORD_PLACEHOLDER);
+    tx.execute(
+        r#"
+        UPDATE OR IGNORE channels
+        SET url = REPLACE(url, ?1, ?2)
+        WHERE source_id = ?3
+        AND instr(url, ?1) > 0
+    "#,
+        params![from, to, source_id],
+    )?;
+    Ok(())
+}
+
+/// Tests share one database per process, so they hold the lock and use their own sources
+#[cfg(test)]
+pub mod test_db {
+    use std::sync::{Mutex, MutexGuard, Once};
+
+    use rusqlite::params;
+
+    use crate::{
+        media_type,
+        types::{Channel, Filters},
+        view_type,
+    };
+
+    static LOCK: Mutex<()> = Mutex::new(());
+    static INIT: Once = Once::new();
+
+    pub fn lock() -> MutexGuard<'static, ()> {
+        let guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        INIT.call_once(|| super::create_or_initialize_db().unwrap());
+        guard
+    }
+
+    pub fn insert_source(name: &str) -> i64 {
+        let sql = super::get_conn().unwrap();
+        sql.execute(
+            "INSERT INTO sources (name, source_type, url, enabled) VALUES (?, 0, 'http://localhost', 1)",
+            params![name],
+        )
+        .unwrap();
+        sql.last_insert_rowid()
+    }
+
+    pub fn channel(source_id: i64, name: &str) -> Channel {
+        Channel {
+            id: None,
+            name: name.to_string(),
+            url: Some(format!("http://localhost/{source_id}/{name}.ts")),
+            group: None,
+            image: None,
+            media_type: media_type::LIVESTREAM,
+            source_id: Some(source_id),
+            series_id: None,
+            group_id: None,
+            favorite: false,
+            stream_id: None,
+            tv_archive: None,
+            season_id: None,
+            episode_num: None,
+            hidden: None,
+            original_name: None,
+            tvg_id: None,
+            progress: None,
+        }
+    }
+
+    pub fn insert_channel(source_id: i64, name: &str) -> Channel {
+        let channel = channel(source_id, name);
+        super::do_tx(|tx| super::insert_channel(tx, channel.clone())).unwrap();
+        let id = super::get_conn()
+            .unwrap()
+            .query_row(
+                "SELECT id FROM channels WHERE source_id = ? AND name = ?",
+                params![source_id, name],
+                |r| r.get(0),
+            )
+            .unwrap();
+        Channel {
+            id: Some(id),
+            ..channel
+        }
+    }
+
+    pub fn filters(source_id: i64) -> Filters {
+        Filters {
+            query: None,
+            source_ids: vec![source_id],
+            media_types: Some(vec![media_type::LIVESTREAM]),
+            view_type: view_type::ALL,
+            page: 1,
+            page_size: None,
+            cursor: None,
+            series_id: None,
+            group_id: None,
+            use_keywords: false,
+            sort: crate::sort_type::ALPHABETICAL_ASC,
+            season: None,
+            exclude_dead: None,
+            fuzzy: None,
+            saved_search_id: None,
+            list_id: None,
+        }
+    }
+
+    pub fn view(source_id: i64, view_type: u8) -> Vec<String> {
+        let mut filters = filters(source_id);
+        filters.view_type = view_type;
+        names(filters)
+    }
+
+    pub fn insert_profile(name: &str) -> i64 {
+        super::save_profile(crate::types::Profile {
+            id: None,
+            name: name.to_string(),
+            volume: None,
+            default_view: None,
+            audio_languages: None,
+            subtitle_languages: None,
+        })
+        .unwrap();
+        super::get_profiles()
+            .unwrap()
+            .into_iter()
+            .find(|p| p.name == name)
+            .and_then(|p| p.id)
+            .unwrap()
+    }
+
+    pub fn names(filters: Filters) -> Vec<String> {
+        super::search(filters)
+            .unwrap()
+            .into_iter()
+            .map(|c| c.name)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test_sql {
+    use super::test_db;
+    use super::*;
+
+    fn get_failures(source_id: i64, url: &str) -> u32 {
+        get_conn()
+            .unwrap()
+            .query_row(
+                "SELECT failures FROM channel_health WHERE source_id = ? AND url = ?",
+                params![source_id, url],
+                |r| r.get(0),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn test_channel_health() {
+        let _lock = test_db::lock();
+        let source_id = test_db::insert_source("health");
+        let flaky = test_db::insert_channel(source_id, "Flaky");
+        let dead = test_db::insert_channel(source_id, "Dead");
+        test_db::insert_channel(source_id, "Healthy");
+        let flaky_url = flaky.url.unwrap();
+        let dead_url = dead.url.unwrap();
+        for _ in 0..3 {
+            set_channel_health(source_id, &flaky_url, false).unwrap();
+            set_channel_health(source_id, &dead_url, false).unwrap();
+        }
+        assert_eq!(get_failures(source_id, &flaky_url), 3);
+        set_channel_health(source_id, &flaky_url, true).unwrap();
+        assert_eq!(get_failures(source_id, &flaky_url), 0);
+        set_channel_health(source_id, &flaky_url, false).unwrap();
+        assert_eq!(get_failures(source_id, &flaky_url), 1);
+
+        let mut filters = test_db::filters(source_id);
+        assert_eq!(
+            test_db::names(filters.clone()),
+            ["Dead", "Flaky", "Healthy"]
+        );
+        filters.exclude_dead = Some(true);
+        assert_eq!(test_db::names(filters), ["Flaky", "Healthy"]);
+    }
+
+    #[test]
+    fn test_switch_profiles() {
+        let _lock = test_db::lock();
+        let initial = get_active_profile().unwrap();
+        let source_id = test_db::insert_source("profiles switch");
+        let first = test_db::insert_channel(source_id, "First").id.unwrap();
+        let second = test_db::insert_channel(source_id, "Second").id.unwrap();
+        let third = test_db::insert_channel(source_id, "Third").id.unwrap();
+        favorite_channel(first, true).unwrap();
+        add_last_watched(second).unwrap();
+
+        let kids = test_db::insert_profile("Kids");
+        switch_profile(kids).unwrap();
+        assert!(test_db::view(source_id, view_type::FAVORITES).is_empty());
+        assert!(test_db::view(source_id, view_type::HISTORY).is_empty());
+        favorite_channel(third, true).unwrap();
+        save_list(UserList {
+            id: None,
+            name: "Cartoons".to_string(),
+        })
+        .unwrap();
+        let list_id = get_lists().unwrap()[0].id.unwrap();
+        add_to_list(list_id, third).unwrap();
+
+        switch_profile(initial).unwrap();
+        assert_eq!(test_db::view(source_id, view_type::FAVORITES), ["First"]);
+        assert_eq!(test_db::view(source_id, view_type::HISTORY), ["Second"]);
+        assert!(remove_from_list(list_id, third).is_err());
+        assert!(reorder_list(list_id, vec![third]).is_err());
+
+        switch_profile(kids).unwrap();
+        assert_eq!(test_db::view(source_id, view_type::FAVORITES), ["Third"]);
+        remove_from_list(list_id, third).unwrap();
+        delete_list(list_id).unwrap();
+        switch_profile(initial).unwrap();
+        delete_profile(kids).unwrap();
+        delete_source(source_id).unwrap();
+    }
+
+    #[test]
+    fn test_refresh_keeps_profiles() {
+        let _lock = test_db::lock();
+        let initial = get_active_profile().unwrap();
+        let source_id = test_db::insert_source("profiles refresh");
+        let first = test_db::insert_channel(source_id, "First").id.unwrap();
+        let second = test_db::insert_channel(source_id, "Second").id.unwrap();
+        let other = test_db::insert_profile("Other");
+        switch_profile(other).unwrap();
+        favorite_channel(first, true).unwrap();
+        add_last_watched(first).unwrap();
+        switch_profile(initial).unwrap();
+        favorite_channel(second, true).unwrap();
+
+        do_tx(|tx| {
+            let preserve = get_preserve(tx, source_id)?;
+            assert!(preserve.iter().any(|p| p.profile_id == Some(other)));
+            wipe(tx, source_id)?;
+            for name in ["First", "Second"] {
+                insert_channel(tx, test_db::channel(source_id, name))?;
+            }
+            restore_preserve(tx, source_id, preserve)
+        })
+        .unwrap();
+
+        assert_eq!(test_db::view(source_id, view_type::FAVORITES), ["Second"]);
+        switch_profile(other).unwrap();
+        assert_eq!(test_db::view(source_id, view_type::FAVORITES), ["First"]);
+        assert_eq!(test_db::view(source_id, view_type::HISTORY), ["First"]);
+        switch_profile(initial).unwrap();
+        delete_profile(other).unwrap();
+        delete_source(source_id).unwrap();
+    }
+
+    #[test]
+    fn test_history_total() {
+        let _lock = test_db::lock();
+        let source_id = test_db::insert_source("history total");
+        let first = test_db::insert_channel(source_id, "First").id.unwrap();
+        test_db::insert_channel(source_id, "Second");
+        let third = test_db::insert_channel(source_id, "Third").id.unwrap();
+        add_last_watched(first).unwrap();
+        add_last_watched(third).unwrap();
+        let mut filters = test_db::filters(source_id);
+        filters.view_type = view_type::HISTORY;
+        filters.page_size = Some(1);
+        let page = search_page(filters).unwrap();
+        assert_eq!(page.total, Some(2));
+        assert_eq!(page.channels.len(), 1);
+        let mut watched = test_db::view(source_id, view_type::HISTORY);
+        watched.sort();
+        assert_eq!(watched, ["First", "Third"]);
+        delete_source(source_id).unwrap();
+    }
+
+    #[test]
+    fn test_substring_search() {
+        let _lock = test_db::lock();
+        let source_id = test_db::insert_source("substring search");
+        test_db::insert_channel(source_id, "FoxNews");
+        test_db::insert_channel(source_id, "Sky Sports FHD");
+        test_db::insert_channel(source_id, "Newsmax");
+        let search = |query: &str, use_keywords: bool| {
+            let mut filters = test_db::filters(source_id);
+            filters.query = Some(query.to_string());
+            filters.use_keywords = use_keywords;
+            filters.fuzzy = Some(true);
+            test_db::names(filters)
+        };
+        assert_eq!(search("hd", true), vec!["Sky Sports FHD"]);
+        assert_eq!(search("news", true), vec!["Newsmax"]);
+        assert_eq!(search("foxnew", false), vec!["FoxNews"]);
+        assert_eq!(search("sky fhd", false), vec!["Sky Sports FHD"]);
+        assert_eq!(search("newsmx", true), vec!["Newsmax"]);
+        assert_eq!(search("source:substring hd", true), vec!["Sky Sports FHD"]);
+        assert!(search("source:substring_search hd", true).is_empty());
+        delete_source(source_id).unwrap();
+    }
+
+    #[test]
+    fn test_merge_duplicates_sql() {
+        let search = query::parse("", false, false).unwrap();
+        let sql = get_merge_duplicates_sql(view_type::FAVORITES, &search, 3);
+        assert!(sql.contains("WHERE d.merge_key = channels.merge_key"));
+        assert!(sql.contains("AND d.id < channels.id"));
+        assert!(sql.contains("AND d.source_id IN (?,?,?)"));
+        assert!(sql.contains("AND favorite = 1"));
+        let sql = get_merge_duplicates_sql(view_type::ALL, &search, 1);
+        assert!(sql.contains("AND d.source_id IN (?)"));
+        assert!(!sql.contains("favorite"));
+        assert!(!sql.contains("last_watched"));
+    }
+}
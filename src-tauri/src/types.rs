@@ -33,6 +33,12 @@ pub struct Channel {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub episode_num: Option<i64>,
     pub hidden: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tvg_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progress: Option<f64>,
 }
 
 #[derive(Clone, PartialEq, Debug, Deserialize, Serialize, Default)]
@@ -66,6 +72,12 @@ pub struct Source {
     pub stream_user_agent: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_updated: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub player: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mirrors: Option<String>,
 }
 
 #[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
@@ -83,6 +95,18 @@ pub struct Settings {
     pub enable_hwdec: Option<bool>,
     pub always_ask_save: Option<bool>,
     pub enable_gpu: Option<bool>,
+    pub merge_duplicates: Option<bool>,
+    pub default_proxy: Option<String>,
+    pub page_size: Option<u16>,
+    pub parental_auto_lock: Option<bool>,
+    pub parental_keywords: Option<String>,
+    pub parental_unlock_minutes: Option<u16>,
+    pub player: Option<u8>,
+    pub custom_player_command: Option<String>,
+    pub audio_languages: Option<String>,
+    pub subtitle_languages: Option<String>,
+    pub playback_retries: Option<u8>,
+    pub playback_retry_delay: Option<u16>,
 }
 
 #[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
@@ -91,12 +115,73 @@ pub struct Filters {
     pub source_ids: Vec<i64>,
     pub media_types: Option<Vec<u8>>,
     pub view_type: u8,
-    pub page: u8,
+    pub page: u32,
+    pub page_size: Option<u16>,
+    pub cursor: Option<SearchCursor>,
     pub series_id: Option<i64>,
     pub group_id: Option<i64>,
     pub use_keywords: bool,
     pub sort: u8,
     pub season: Option<i64>,
+    pub exclude_dead: Option<bool>,
+    pub fuzzy: Option<bool>,
+    pub saved_search_id: Option<i64>,
+    pub list_id: Option<i64>,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+pub struct PlayerEvent {
+    pub kind: String,
+    pub channel_id: Option<i64>,
+    pub message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_kind: Option<u8>,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+pub struct SearchCursor {
+    pub name: String,
+    pub id: i64,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+pub struct SearchPage {
+    pub channels: Vec<Channel>,
+    pub total: Option<u64>,
+    pub next_cursor: Option<SearchCursor>,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+pub struct Profile {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    pub name: String,
+    pub volume: Option<u8>,
+    pub default_view: Option<u8>,
+    pub audio_languages: Option<String>,
+    pub subtitle_languages: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+pub struct UserList {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    pub name: String,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+pub struct SavedSearch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    pub name: String,
+    pub filters: Filters,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+pub struct ExportedSavedSearch {
+    pub search: SavedSearch,
+    pub sources: Vec<String>,
+    pub group: Option<String>,
 }
 
 #[derive(Clone, PartialEq, Debug, Deserialize, Serialize, Default)]
@@ -109,6 +194,24 @@ pub struct ChannelHttpHeaders {
     pub user_agent: Option<String>,
     pub http_origin: Option<String>,
     pub ignore_ssl: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cookies: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extra_headers: Option<Vec<HttpHeader>>,
+}
+
+#[derive(Clone, PartialEq, Debug, Default, Deserialize, Serialize)]
+pub struct ChannelDrm {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel_id: Option<i64>,
+    pub license_type: Option<String>,
+    pub license_key: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+pub struct HttpHeader {
+    pub name: String,
+    pub value: String,
 }
 
 #[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
@@ -154,6 +257,17 @@ pub struct ExportedSource {
     pub channels: Vec<CustomChannel>,
 }
 
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+pub struct NameRule {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_id: Option<i64>,
+    pub rule_type: u8,
+    pub pattern: Option<String>,
+    pub replacement: Option<String>,
+}
+
 #[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
 pub struct EPG {
     pub epg_id: String,
@@ -162,6 +276,8 @@ pub struct EPG {
     pub start_time: String,
     pub start_timestamp: i64,
     pub end_time: String,
+    #[serde(default)]
+    pub end_timestamp: i64,
     pub timeshift_url: Option<String>,
     pub has_archive: bool,
     pub now_playing: bool,
@@ -182,6 +298,10 @@ pub struct AppState {
     pub restream_stop_signal: Arc<AtomicBool>,
 
     pub play_stop: HashMap<i64, IndexMap<String, CancellationToken>>,
+    pub playback_stop: CancellationToken,
+    pub health_check_stop: Option<CancellationToken>,
+    pub sleep_timer_stop: Option<CancellationToken>,
+    pub sleep_timer_end: Option<i64>,
 }
 
 #[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
@@ -199,4 +319,32 @@ pub struct ChannelPreserve {
     pub hidden: Option<bool>,
     #[serde(default)]
     pub is_group: bool,
+    #[serde(default)]
+    pub position: Option<i64>,
+    #[serde(default)]
+    pub locked: bool,
+    #[serde(default)]
+    pub playback_overrides: Option<String>,
+    /// Set for the state of a profile other than the active one
+    #[serde(default)]
+    pub profile_id: Option<i64>,
+}
+
+#[derive(Clone, PartialEq, Debug, Default, Deserialize, Serialize)]
+pub struct PlaybackOverrides {
+    pub mpv_params: Option<String>,
+    pub use_stream_caching: Option<bool>,
+    pub enable_hwdec: Option<bool>,
+    pub deinterlace: Option<bool>,
+    pub aspect_ratio: Option<String>,
+    pub cache_secs: Option<u32>,
+    pub audio_languages: Option<String>,
+    pub subtitle_languages: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+pub struct HealthCheckProgress {
+    pub checked: usize,
+    pub total: usize,
+    pub dead: usize,
 }
@@ -3,3 +3,6 @@ pub const FAVORITES: u8 = 1;
 pub const CATEGORIES: u8 = 2;
 pub const HISTORY: u8 = 3;
 pub const HIDDEN: u8 = 4;
+pub const SAVED_SEARCHES: u8 = 5;
+pub const LIST: u8 = 6;
+pub const CONTINUE_WATCHING: u8 = 7;
@@ -1,5 +1,8 @@
+use crate::crypto;
 use crate::log;
 use crate::media_type;
+use crate::name_rules::NameRewriter;
+use crate::parental;
 use crate::sql;
 use crate::sql::insert_season;
 use crate::types::Channel;
@@ -8,7 +11,7 @@ use crate::types::EPG;
 use crate::types::Season;
 use crate::types::Source;
 use crate::utils::get_local_time;
-use crate::utils::get_user_agent_from_source;
+use crate::utils::get_source_client;
 use anyhow::anyhow;
 use anyhow::{Context, Result};
 use base64::Engine;
@@ -35,6 +38,7 @@ const GET_VOD_CATEGORIES: &str = "get_vod_categories";
 const GET_EPG: &str = "get_simple_data_table";
 const LIVE_STREAM_EXTENSION: &str = "ts";
 const NO_SEASON_NUMBER: i64 = -9999;
+pub const PASSWORD_PLACEHOLDER: &str = "{password}";
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 struct XtreamStream {
@@ -50,6 +54,8 @@ struct XtreamStream {
     container_extension: Option<String>,
     #[serde(default)]
     tv_archive: serde_json::Value,
+    #[serde(default)]
+    epg_channel_id: serde_json::Value,
 }
 #[derive(Serialize, Deserialize, Clone, Debug)]
 struct XtreamSeries {
@@ -121,31 +127,24 @@ fn build_xtream_url(source: &mut Source) -> Result<Url> {
             "username",
             &source.username.clone().context("Missing username")?,
         )
-        .append_pair(
-            "password",
-            &source.password.clone().context("Missing password")?,
-        );
+        .append_pair("password", crypto::get_password(source.password.as_ref())?);
     Ok(url)
 }
 
 pub async fn get_xtream(mut source: Source, wipe: bool) -> Result<()> {
     let url = build_xtream_url(&mut source)?;
-    let user_agent = get_user_agent_from_source(&source)?;
+    let client = get_source_client(&source)?;
     let (live, live_cats, vods, vods_cats, series, series_cats) = join!(
-        get_xtream_http_data::<Vec<XtreamStream>>(url.clone(), GET_LIVE_STREAMS, &user_agent),
+        get_xtream_http_data::<Vec<XtreamStream>>(url.clone(), GET_LIVE_STREAMS, &client),
         get_xtream_http_data::<Vec<XtreamCategory>>(
             url.clone(),
             GET_LIVE_STREAM_CATEGORIES,
-            &user_agent
-        ),
-        get_xtream_http_data::<Vec<XtreamStream>>(url.clone(), GET_VODS, &user_agent),
-        get_xtream_http_data::<Vec<XtreamCategory>>(url.clone(), GET_VOD_CATEGORIES, &user_agent),
-        get_xtream_http_data::<Vec<XtreamStream>>(url.clone(), GET_SERIES, &user_agent),
-        get_xtream_http_data::<Vec<XtreamCategory>>(
-            url.clone(),
-            GET_SERIES_CATEGORIES,
-            &user_agent
+            &client
         ),
+        get_xtream_http_data::<Vec<XtreamStream>>(url.clone(), GET_VODS, &client),
+        get_xtream_http_data::<Vec<XtreamCategory>>(url.clone(), GET_VOD_CATEGORIES, &client),
+        get_xtream_http_data::<Vec<XtreamStream>>(url.clone(), GET_SERIES, &client),
+        get_xtream_http_data::<Vec<XtreamCategory>>(url.clone(), GET_SERIES_CATEGORIES, &client),
     );
     let mut sql = sql::get_conn()?;
     let tx = sql.transaction()?;
@@ -157,14 +156,34 @@ pub async fn get_xtream(mut source: Source, wipe: bool) -> Result<()> {
     } else {
         source.id = Some(sql::create_or_find_source_by_name(&tx, &source)?);
     }
+    let name_rewriter = NameRewriter::new(sql::get_name_rules(
+        &tx,
+        source.id.context("no source id")?,
+    )?)?;
     let mut fail_count = 0;
-    live.and_then(|live| process_xtream(&tx, live, live_cats?, &source, media_type::LIVESTREAM))
-        .unwrap_or_else(|e| {
-            log::log(format!("{:?}", e.context("Failed to process live")));
-            fail_count += 1;
-        });
+    live.and_then(|live| {
+        process_xtream(
+            &tx,
+            live,
+            live_cats?,
+            &source,
+            media_type::LIVESTREAM,
+            &name_rewriter,
+        )
+    })
+    .unwrap_or_else(|e| {
+        log::log(format!("{:?}", e.context("Failed to process live")));
+        fail_count += 1;
+    });
     vods.and_then(|vods: Vec<XtreamStream>| {
-        process_xtream(&tx, vods, vods_cats?, &source, media_type::MOVIE)
+        process_xtream(
+            &tx,
+            vods,
+            vods_cats?,
+            &source,
+            media_type::MOVIE,
+            &name_rewriter,
+        )
     })
     .unwrap_or_else(|e| {
         log::log(format!("{:?}", e.context("Failed to process vods")));
@@ -172,7 +191,14 @@ pub async fn get_xtream(mut source: Source, wipe: bool) -> Result<()> {
     });
     series
         .and_then(|series: Vec<XtreamStream>| {
-            process_xtream(&tx, series, series_cats?, &source, media_type::SERIE)
+            process_xtream(
+                &tx,
+                series,
+                series_cats?,
+                &source,
+                media_type::SERIE,
+                &name_rewriter,
+            )
         })
         .unwrap_or_else(|e| {
             log::log(format!("{:?}", e.context("Failed to process series")));
@@ -188,16 +214,16 @@ pub async fn get_xtream(mut source: Source, wipe: bool) -> Result<()> {
     if wipe {
         sql::restore_preserve(&tx, source.id.context("no source id")?, channel_preserve)?;
     }
+    parental::auto_lock(&tx, source.id.context("no source id")?)?;
     sql::analyze(&tx)?;
     tx.commit()?;
     Ok(())
 }
 
-async fn get_xtream_http_data<T>(mut url: Url, action: &str, user_agent: &String) -> Result<T>
+async fn get_xtream_http_data<T>(mut url: Url, action: &str, client: &Client) -> Result<T>
 where
     T: serde::de::DeserializeOwned,
 {
-    let client = Client::builder().user_agent(user_agent).build()?;
     url.query_pairs_mut().append_pair("action", action);
     let data = client.get(url).send().await?.json::<T>().await?;
     Ok(data)
@@ -209,6 +235,7 @@ fn process_xtream(
     cats: Vec<XtreamCategory>,
     source: &Source,
     stream_type: u8,
+    name_rewriter: &NameRewriter,
 ) -> Result<()> {
     let cats: HashMap<String, String> = cats
         .into_iter()
@@ -220,19 +247,20 @@ fn process_xtream(
     let mut groups: HashMap<String, i64> = HashMap::new();
     for live in streams {
         let category_name = get_cat_name(&cats, get_serde_json_string(&live.category_id));
-        convert_xtream_live_to_channel(live, &source, stream_type.clone(), category_name)
-            .and_then(|mut channel| {
-                sql::set_channel_group_id(
-                    &mut groups,
-                    &mut channel,
-                    &tx,
-                    source.id.as_ref().unwrap(),
-                )
+        convert_xtream_live_to_channel(
+            live,
+            &source,
+            stream_type.clone(),
+            category_name,
+            name_rewriter,
+        )
+        .and_then(|mut channel| {
+            sql::set_channel_group_id(&mut groups, &mut channel, &tx, source.id.as_ref().unwrap())
                 .unwrap_or_else(|e| log::log(format!("{:?}", e)));
-                sql::insert_channel(&tx, channel)?;
-                Ok(())
-            })
-            .unwrap_or_else(|e| log::log(format!("{:?}", e)));
+            sql::insert_channel(&tx, channel)?;
+            Ok(())
+        })
+        .unwrap_or_else(|e| log::log(format!("{:?}", e)));
     }
     Ok(())
 }
@@ -249,8 +277,11 @@ fn convert_xtream_live_to_channel(
     source: &Source,
     stream_type: u8,
     category_name: Option<String>,
+    name_rewriter: &NameRewriter,
 ) -> Result<Channel> {
     let stream_id = get_serde_json_u64(&stream.stream_id);
+    let name = stream.name.context("No name")?.trim().to_string();
+    let rewritten_name = name_rewriter.rewrite(&name);
     Ok(Channel {
         id: None,
         group: category_name.map(|x| x.trim().to_string()),
@@ -259,7 +290,10 @@ fn convert_xtream_live_to_channel(
             .or(stream.cover)
             .map(|x| x.trim().to_string()),
         media_type: stream_type.clone(),
-        name: stream.name.context("No name")?.trim().to_string(),
+        original_name: (rewritten_name != name).then_some(name),
+        name: rewritten_name,
+        tvg_id: get_serde_json_string(&stream.epg_channel_id).filter(|x| !x.is_empty()),
+        progress: None,
         source_id: source.id,
         url: if stream_type == media_type::SERIE {
             get_serde_json_string(&stream.series_id)
@@ -293,12 +327,20 @@ fn get_url(
         source.url_origin.clone().unwrap(),
         get_media_type_string(stream_type)?,
         source.username.clone().unwrap(),
-        source.password.clone().unwrap(),
+        PASSWORD_PLACEHOLDER,
         stream_id,
         extension.unwrap_or(LIVE_STREAM_EXTENSION.to_string())
     ))
 }
 
+pub fn resolve_url(url: String, source: Option<&Source>) -> Result<String> {
+    if !url.contains(PASSWORD_PLACEHOLDER) {
+        return Ok(url);
+    }
+    let password = crypto::get_password(source.and_then(|s| s.password.as_ref()))?;
+    Ok(url.replace(PASSWORD_PLACEHOLDER, password))
+}
+
 fn get_media_type_string(stream_type: u8) -> Result<String> {
     match stream_type {
         media_type::LIVESTREAM => Ok("live".to_string()),
@@ -320,11 +362,10 @@ pub async fn get_episodes(channel: Channel) -> Result<()> {
     }
     let mut source = sql::get_source_from_id(channel.source_id.context("no source id")?)?;
     let mut url = build_xtream_url(&mut source)?;
-    let user_agent = get_user_agent_from_source(&source)?;
+    let client = get_source_client(&source)?;
     url.query_pairs_mut()
         .append_pair("series_id", &series_id.to_string());
-    let mut series =
-        get_xtream_http_data::<XtreamSeries>(url, GET_SERIES_INFO, &user_agent).await?;
+    let mut series = get_xtream_http_data::<XtreamSeries>(url, GET_SERIES_INFO, &client).await?;
     let mut episodes: Vec<XtreamEpisode> = series
         .episodes
         .into_values()
@@ -503,16 +544,19 @@ fn episode_to_channel(
         favorite: false,
         tv_archive: None,
         hidden: Some(false),
+        original_name: None,
+        tvg_id: None,
+        progress: None,
     })
 }
 
 pub async fn get_epg(channel: Channel) -> Result<Vec<EPG>> {
     let mut source = sql::get_source_from_id(channel.source_id.context("no source id")?)?;
     let mut url = build_xtream_url(&mut source)?;
-    let user_agent = get_user_agent_from_source(&source)?;
+    let client = get_source_client(&source)?;
     let stream_id = channel.stream_id.context("No stream id")?.to_string();
     url.query_pairs_mut().append_pair("stream_id", &stream_id);
-    let epg: XtreamEPG = get_xtream_http_data(url, GET_EPG, &user_agent).await?;
+    let epg: XtreamEPG = get_xtream_http_data(url, GET_EPG, &client).await?;
     let url = get_timeshift_url_base(&source)?;
     let current_time = Local::now();
     let mut otv_epgs = Vec::new();
@@ -536,6 +580,8 @@ fn is_valid_epg(epg: &EPG, now: &DateTime<Local>) -> Result<bool> {
 fn xtream_epg_to_epg(epg: XtreamEPGItem, url: &Url, stream_id: &str) -> Result<EPG> {
     let start_timestamp =
         get_serde_json_i64(&epg.start_timestamp).context("no valid start timestamp")?;
+    let end_timestamp =
+        get_serde_json_i64(&epg.stop_timestamp).context("no valid end timestamp")?;
     Ok(EPG {
         epg_id: get_serde_json_string(&epg.id).context("no epg id")?,
         title: String::from_utf8(BASE64_STANDARD.decode(&epg.title)?)?,
@@ -543,12 +589,11 @@ fn xtream_epg_to_epg(epg: XtreamEPGItem, url: &Url, stream_id: &str) -> Result<E
         start_time: get_local_time(start_timestamp)?
             .format("%B %d, %H:%M")
             .to_string(),
-        end_time: get_local_time(
-            get_serde_json_i64(&epg.stop_timestamp).context("no valid end timestamp")?,
-        )?
-        .format("%B %d, %H:%M")
-        .to_string(),
+        end_time: get_local_time(end_timestamp)?
+            .format("%B %d, %H:%M")
+            .to_string(),
         start_timestamp,
+        end_timestamp,
         timeshift_url: if epg.has_archive == 1 {
             Some(get_timeshift_url(
                 url.clone(),
@@ -571,7 +616,7 @@ fn get_timeshift_url_base(source: &Source) -> Result<Url> {
         .extend(&["streaming", "timeshift.php"]);
     url.query_pairs_mut()
         .append_pair("username", source.username.as_ref().context("no username")?)
-        .append_pair("password", source.password.as_ref().context("no password")?);
+        .append_pair("password", crypto::get_password(source.password.as_ref())?);
     Ok(url)
 }
 
@@ -588,3 +633,36 @@ fn get_timeshift_url(mut url: Url, start: String, end: String, stream_id: &str)
         .append_pair("duration", &duration);
     Ok(url.to_string())
 }
+
+#[cfg(test)]
+mod test_xtream {
+    use super::{XtreamStream, get_serde_json_string};
+
+    #[test]
+    fn test_epg_channel_id() {
+        let streams: Vec<XtreamStream> = serde_json::from_str(
+            r#"[
+                {"stream_id": 1, "name": "A", "epg_channel_id": " bbc.uk "},
+                {"stream_id": 2, "name": "B", "epg_channel_id": 1234},
+                {"stream_id": 3, "name": "C", "epg_channel_id": false},
+                {"stream_id": 4, "name": "D", "epg_channel_id": null},
+                {"stream_id": 5, "name": "E"}
+            ]"#,
+        )
+        .unwrap();
+        let ids: Vec<Option<String>> = streams
+            .iter()
+            .map(|s| get_serde_json_string(&s.epg_channel_id))
+            .collect();
+        assert_eq!(
+            ids,
+            [
+                Some("bbc.uk".to_string()),
+                Some("1234".to_string()),
+                None,
+                None,
+                None
+            ]
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use anyhow::{Context, Result, bail};
+use tauri::{AppHandle, State};
+use tokio::sync::Mutex;
+
+use crate::{
+    media_type, mpv, sql,
+    types::{AppState, Channel, Filters},
+};
+
+const ZAP_PAGE_SIZE: u16 = 500;
+
+fn get_page(filters: &Filters, page: u32) -> Result<Vec<Channel>> {
+    let mut filters = filters.clone();
+    filters.page = page;
+    filters.page_size = Some(ZAP_PAGE_SIZE);
+    filters.cursor = None;
+    sql::search(filters)
+}
+
+fn is_playable(channel: &Channel) -> bool {
+    channel.url.is_some()
+        && (channel.media_type == media_type::LIVESTREAM || channel.media_type == media_type::MOVIE)
+}
+
+/// Walks the browsed list to find the channel next to the current one, wrapping at both ends
+fn find_adjacent(filters: &Filters, channel_id: i64, forward: bool) -> Result<Channel> {
+    let mut first = None;
+    let mut last: Option<Channel> = None;
+    let mut found = false;
+    let mut page = 1;
+    loop {
+        let channels = get_page(filters, page)?;
+        let has_more = channels.len() == ZAP_PAGE_SIZE as usize;
+        for channel in channels.into_iter().filter(is_playable) {
+            if found && forward {
+                return Ok(channel);
+            }
+            if channel.id == Some(channel_id) {
+                found = true;
+                if !forward && let Some(previous) = last.take() {
+                    return Ok(previous);
+                }
+            }
+            if first.is_none() {
+                first = Some(channel.clone());
+            }
+            last = Some(channel);
+        }
+        if !has_more {
+            break;
+        }
+        page += 1;
+    }
+    match forward {
+        true => first,
+        false => last,
+    }
+    .context("No channel to switch to")
+}
+
+/// Numbers only count playable channels, the same ones zapping goes through
+fn find_at(filters: &Filters, number: u32) -> Result<Channel> {
+    if number == 0 {
+        bail!("Channel numbers start at 1");
+    }
+    let mut remaining = number as usize - 1;
+    let mut page = 1;
+    loop {
+        let channels = get_page(filters, page)?;
+        let has_more = channels.len() == ZAP_PAGE_SIZE as usize;
+        let mut playable: Vec<Channel> = channels.into_iter().filter(is_playable).collect();
+        if remaining < playable.len() {
+            return Ok(playable.swap_remove(remaining));
+        }
+        remaining -= playable.len();
+        if !has_more {
+            bail!("No channel at position {number}");
+        }
+        page += 1;
+    }
+}
+
+pub async fn zap_next(
+    filters: Filters,
+    channel_id: i64,
+    state: State<'_, Mutex<AppState>>,
+    app: AppHandle,
+) -> Result<Channel> {
+    let channel = find_adjacent(&filters, channel_id, true)?;
+    mpv::zap(channel.clone(), state, app).await?;
+    Ok(channel)
+}
+
+pub async fn zap_previous(
+    filters: Filters,
+    channel_id: i64,
+    state: State<'_, Mutex<AppState>>,
+    app: AppHandle,
+) -> Result<Channel> {
+    let channel = find_adjacent(&filters, channel_id, false)?;
+    mpv::zap(channel.clone(), state, app).await?;
+    Ok(channel)
+}
+
+pub async fn zap_to(
+    filters: Filters,
+    number: u32,
+    state: State<'_, Mutex<AppState>>,
+    app: AppHandle,
+) -> Result<Channel> {
+    let channel = find_at(&filters, number)?;
+    mpv::zap(channel.clone(), state, app).await?;
+    Ok(channel)
+}
+
+#[cfg(test)]
+mod test_zapping {
+    use super::*;
+    use crate::sql::{self, test_db};
+
+    /// Bravo has no url so it's skipped by zapping and numbering
+    fn insert_channels(source_id: i64) -> Vec<i64> {
+        let ids: Vec<i64> = ["Alpha", "Bravo", "Charlie", "Delta"]
+            .iter()
+            .map(|name| test_db::insert_channel(source_id, name).id.unwrap())
+            .collect();
+        sql::get_conn()
+            .unwrap()
+            .execute("UPDATE channels SET url = NULL WHERE id = ?", [ids[1]])
+            .unwrap();
+        ids
+    }
+
+    #[test]
+    fn test_find_at() {
+        let _lock = test_db::lock();
+        let source_id = test_db::insert_source("zapping numbers");
+        insert_channels(source_id);
+        let filters = test_db::filters(source_id);
+        assert_eq!(find_at(&filters, 1).unwrap().name, "Alpha");
+        assert_eq!(find_at(&filters, 2).unwrap().name, "Charlie");
+        assert_eq!(find_at(&filters, 3).unwrap().name, "Delta");
+        assert!(find_at(&filters, 4).is_err());
+        assert!(find_at(&filters, 0).is_err());
+        sql::delete_source(source_id).unwrap();
+    }
+
+    #[test]
+    fn test_find_adjacent() {
+        let _lock = test_db::lock();
+        let source_id = test_db::insert_source("zapping adjacent");
+        let ids = insert_channels(source_id);
+        let filters = test_db::filters(source_id);
+        let next = |id: i64| find_adjacent(&filters, id, true).unwrap().name;
+        let previous = |id: i64| find_adjacent(&filters, id, false).unwrap().name;
+        assert_eq!(next(ids[0]), "Charlie");
+        assert_eq!(previous(ids[2]), "Alpha");
+        assert_eq!(next(ids[3]), "Alpha");
+        assert_eq!(previous(ids[0]), "Delta");
+        sql::delete_source(source_id).unwrap();
+    }
+}